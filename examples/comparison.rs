@@ -35,7 +35,7 @@ fn main() {
 
     // Sign the email using the mini-mail-auth crate.
     let signed_email_mini_mail_auth =
-        mini_mail_auth::sign_email(email, domain, selector, &private_key);
+        mini_mail_auth::try_sign_email(email, domain, selector, &private_key).unwrap();
 
     // Compare the two signatures.
     println!("--- mail-auth output ---");
@@ -0,0 +1,97 @@
+use super::{ArcSeal, ArcSet, ChainValidation};
+use crate::common::{
+    crypto::Algorithm,
+    headers::{HeaderWriter, Writer},
+};
+
+impl ArcSeal {
+    pub fn write(&self, writer: &mut impl Writer, as_header: bool) {
+        // Seals are always signed with relaxed header canonicalization
+        let (header, new_line) = if as_header {
+            (&b"ARC-Seal: "[..], &b"\r\n\t"[..])
+        } else {
+            (&b"arc-seal:"[..], &b" "[..])
+        };
+        writer.write(header);
+        writer.write(format!("i={}; a=", self.i).as_bytes());
+        writer.write(match self.a {
+            Algorithm::RsaSha256 => &b"rsa-sha256"[..],
+            Algorithm::Ed25519Sha256 => b"ed25519-sha256",
+        });
+        if self.t > 0 {
+            writer.write(format!("; t={}", self.t).as_bytes());
+        }
+        writer.write(b"; cv=");
+        writer.write(self.cv.as_str().as_bytes());
+        writer.write(b";");
+        writer.write(new_line);
+
+        let mut bw = 1;
+        for (tag, value) in [(&b"d="[..], &self.d), (&b"; s="[..], &self.s)] {
+            writer.write_len(tag, &mut bw);
+            writer.write_len(value.as_bytes(), &mut bw);
+        }
+        writer.write_len(b";", &mut bw);
+        writer.write(new_line);
+        bw = 1;
+
+        writer.write_len(b"b=", &mut bw);
+        for &byte in &self.b {
+            writer.write_len(&[byte], &mut bw);
+            if bw >= 76 {
+                writer.write(new_line);
+                bw = 1;
+            }
+        }
+
+        writer.write(b";");
+        if as_header {
+            writer.write(b"\r\n");
+        }
+    }
+}
+
+impl ArcSet {
+    /// Writes the `ARC-Message-Signature` header of this set.
+    pub fn write_signature(&self, writer: &mut impl Writer, as_header: bool) {
+        self.signature.write_as(
+            writer,
+            "ARC-Message-Signature",
+            format!("i={}", self.instance).as_bytes(),
+            as_header,
+        );
+    }
+
+    /// Writes the `ARC-Authentication-Results` header of this set.
+    pub fn write_results(&self, writer: &mut impl Writer) {
+        writer.write(format!("ARC-Authentication-Results: i={}; ", self.instance).as_bytes());
+        writer.write(self.results.trim_end().as_bytes());
+        writer.write(b"\r\n");
+    }
+}
+
+/// Writes the set as it is prepended to the message: the `ARC-Seal` first,
+/// followed by the `ARC-Message-Signature` and `ARC-Authentication-Results`.
+impl HeaderWriter for ArcSet {
+    fn write_header(&self, writer: &mut impl Writer) {
+        self.seal.write(writer, true);
+        self.write_signature(writer, true);
+        self.write_results(writer);
+    }
+}
+
+impl HeaderWriter for ArcSeal {
+    fn write_header(&self, writer: &mut impl Writer) {
+        self.write(writer, true);
+    }
+}
+
+impl ChainValidation {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ChainValidation::None => "none",
+            ChainValidation::Pass => "pass",
+            ChainValidation::Fail => "fail",
+        }
+    }
+}
@@ -0,0 +1,85 @@
+pub mod headers;
+pub mod parse;
+pub mod seal;
+pub mod verify;
+
+use crate::{
+    common::crypto::Algorithm,
+    dkim::{DkimSigner, DkimVerifier, Done, Signature},
+    Error,
+};
+
+/// The maximum number of ARC sets a message may carry (RFC 8617, section 4.2.1).
+pub const MAX_ARC_SETS: u32 = 50;
+
+/// The chain validation status (`cv=`) recorded in an `ARC-Seal`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ChainValidation {
+    /// There were no ARC sets before this one.
+    #[default]
+    None,
+    Pass,
+    Fail,
+}
+
+/// An `ARC-Seal` header (RFC 8617, section 4.1.3).
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct ArcSeal {
+    pub i: u32,
+    pub a: Algorithm,
+    pub b: Vec<u8>,
+    pub cv: ChainValidation,
+    pub d: String,
+    pub s: String,
+    pub t: u64,
+}
+
+/// The three headers added by an ARC sealer: the `ARC-Authentication-Results`
+/// copied from the sealer's own `Authentication-Results`, the
+/// `ARC-Message-Signature` and the `ARC-Seal`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ArcSet {
+    /// The instance number (`i=`) shared by the three headers.
+    pub instance: u32,
+    /// The `ARC-Authentication-Results` payload following `i=`, starting with
+    /// the authentication service identifier.
+    pub results: String,
+    /// The `ARC-Message-Signature`, whose `i=` is the set's instance rather
+    /// than an agent or user identifier.
+    pub signature: Signature,
+    pub seal: ArcSeal,
+}
+
+/// Adds ARC sets to forwarded messages (RFC 8617), signing with the key,
+/// domain, selector and headers of a [`DkimSigner`].
+pub struct ArcSealer<T> {
+    pub(crate) signer: DkimSigner<T, Done>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ArcResult {
+    /// The message has no ARC sets.
+    None,
+    Pass,
+    Fail(Error),
+    TempError(Error),
+}
+
+/// The status of a message's ARC chain, returned by [`ArcVerifier::verify`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ArcOutput {
+    pub result: ArcResult,
+    /// The seals of the chain in instance order, when they could be parsed.
+    pub seals: Vec<ArcSeal>,
+    /// The instance of the oldest `ARC-Message-Signature` that still validates
+    /// along with every newer one, or 0 when all of them validate (RFC 8617,
+    /// section 5.2).
+    pub oldest_pass: u32,
+}
+
+/// Validates the ARC chains of received messages (RFC 8617, section 5.2),
+/// fetching keys through the same [`Resolver`](crate::Resolver) as DKIM.
+#[derive(Debug, Clone, Default)]
+pub struct ArcVerifier<R> {
+    pub(crate) verifier: DkimVerifier<R>,
+}
@@ -0,0 +1,150 @@
+use super::{ArcSeal, ChainValidation, MAX_ARC_SETS};
+use crate::{
+    common::{crypto::Algorithm, parse::TagList},
+    dkim::parse::{parse_base64, parse_number, parse_token, strip_field_name, tag_name},
+    Error,
+};
+
+impl ArcSeal {
+    /// Parses an `ARC-Seal` header. `header` may either be the header value
+    /// alone or the complete header including its field name.
+    pub fn parse(header: &[u8]) -> crate::Result<Self> {
+        let header = strip_field_name(header, b"ARC-Seal");
+        let mut seal = ArcSeal::default();
+        let mut seen_tags: Vec<&[u8]> = Vec::new();
+
+        for tag in TagList::new(header) {
+            let (name, value) = tag?;
+            if seen_tags.contains(&name) {
+                return Err(Error::DuplicateTag(tag_name(name)));
+            }
+            seen_tags.push(name);
+
+            match name {
+                b"i" => seal.i = parse_instance_number(name, value)?,
+                b"a" => seal.a = Algorithm::parse(value)?,
+                b"b" => seal.b = parse_base64(name, value)?,
+                b"cv" => seal.cv = ChainValidation::parse(value)?,
+                b"d" => seal.d = parse_token(name, value)?,
+                b"s" => seal.s = parse_token(name, value)?,
+                b"t" => seal.t = parse_number(name, value)?,
+                // Seals cover the ARC sets only, never a list of headers
+                b"h" => return Err(Error::InvalidTag(tag_name(name))),
+                _ => (),
+            }
+        }
+
+        for required in ["i", "a", "b", "cv", "d", "s"] {
+            if !seen_tags.contains(&required.as_bytes()) {
+                return Err(Error::MissingTag(required.to_string()));
+            }
+        }
+
+        Ok(seal)
+    }
+}
+
+impl ChainValidation {
+    pub(crate) fn parse(value: &[u8]) -> crate::Result<Self> {
+        if value.eq_ignore_ascii_case(b"none") {
+            Ok(ChainValidation::None)
+        } else if value.eq_ignore_ascii_case(b"pass") {
+            Ok(ChainValidation::Pass)
+        } else if value.eq_ignore_ascii_case(b"fail") {
+            Ok(ChainValidation::Fail)
+        } else {
+            Err(Error::InvalidTag("cv".to_string()))
+        }
+    }
+}
+
+/// The headers of one ARC set.
+#[derive(Default)]
+pub(crate) struct ArcSetHeaders<'x> {
+    pub(crate) results: Option<(&'x [u8], &'x [u8])>,
+    pub(crate) signature: Option<(&'x [u8], &'x [u8])>,
+    pub(crate) seal: Option<(&'x [u8], &'x [u8])>,
+}
+
+impl<'x> ArcSetHeaders<'x> {
+    /// The headers of the set in the order they are hashed by a seal.
+    pub(crate) fn iter(&self) -> impl Iterator<Item = (&'x [u8], &'x [u8])> {
+        [self.results, self.signature, self.seal]
+            .into_iter()
+            .flatten()
+    }
+}
+
+/// Groups the ARC headers of a message into sets ordered by instance, checking
+/// that every instance from 1 up to the last one has exactly one header of
+/// each kind.
+pub(crate) fn arc_sets<'x>(
+    headers: &[(&'x [u8], &'x [u8])],
+) -> crate::Result<Vec<ArcSetHeaders<'x>>> {
+    let mut sets: Vec<ArcSetHeaders<'x>> = Vec::new();
+
+    for &(name, value) in headers {
+        let field_name = name.trim_ascii_end();
+        let (instance, kind) = if field_name.eq_ignore_ascii_case(b"ARC-Seal") {
+            (parse_instance(value, false), 2)
+        } else if field_name.eq_ignore_ascii_case(b"ARC-Message-Signature") {
+            (parse_instance(value, false), 1)
+        } else if field_name.eq_ignore_ascii_case(b"ARC-Authentication-Results") {
+            (parse_instance(value, true), 0)
+        } else {
+            continue;
+        };
+        let instance = match instance {
+            Some(instance) if instance > u64::from(MAX_ARC_SETS) => {
+                return Err(Error::ArcChainTooLong)
+            }
+            Some(instance) if instance > 0 => instance as usize,
+            _ => return Err(Error::InvalidArcChain),
+        };
+
+        if sets.len() < instance {
+            sets.resize_with(instance, Default::default);
+        }
+        let set = &mut sets[instance - 1];
+        let slot = match kind {
+            0 => &mut set.results,
+            1 => &mut set.signature,
+            _ => &mut set.seal,
+        };
+        if slot.replace((name, value)).is_some() {
+            return Err(Error::InvalidArcChain);
+        }
+    }
+
+    if sets
+        .iter()
+        .any(|set| set.results.is_none() || set.signature.is_none() || set.seal.is_none())
+    {
+        return Err(Error::InvalidArcChain);
+    }
+
+    Ok(sets)
+}
+
+/// Reads the `i=` tag of an ARC header. `ARC-Authentication-Results` headers
+/// must start with it, as the rest of their value is not a tag-list.
+fn parse_instance(value: &[u8], first_only: bool) -> Option<u64> {
+    let mut tags = TagList::new(value);
+    let tag = if first_only {
+        tags.next().and_then(|tag| tag.ok())
+    } else {
+        tags.filter_map(|tag| tag.ok())
+            .find(|(name, _)| *name == b"i")
+    };
+    tag.filter(|(name, _)| *name == b"i")
+        .and_then(|(name, value)| parse_number(name, value).ok())
+}
+
+fn parse_instance_number(name: &[u8], value: &[u8]) -> crate::Result<u32> {
+    parse_number(name, value).and_then(|instance| {
+        u32::try_from(instance)
+            .ok()
+            .filter(|instance| (1..=MAX_ARC_SETS).contains(instance))
+            .ok_or_else(|| Error::InvalidTag(tag_name(name)))
+    })
+}
@@ -0,0 +1,279 @@
+use super::{
+    parse::{arc_sets, ArcSetHeaders},
+    ArcSeal, ArcSealer, ArcSet, ChainValidation, MAX_ARC_SETS,
+};
+use crate::{
+    common::{
+        clock::{Clock, SystemClock},
+        crypto::{HashAlgorithm, SigningKey},
+        headers::{HeaderIterator, Writable, Writer},
+    },
+    dkim::{canonicalize::CanonicalHeaders, Canonicalization, DkimSigner, Done},
+    Error,
+};
+use base64::{engine::general_purpose::STANDARD as BASE64_STANDARD, Engine as _};
+
+impl<T> ArcSealer<T> {
+    /// Creates a sealer signing the `ARC-Message-Signature` the way `signer`
+    /// signs a `DKIM-Signature`, with its key, domain, selector, headers and
+    /// canonicalization. `ARC-Seal` headers are never signed and the agent or
+    /// user identifier is not used.
+    pub fn new(mut signer: DkimSigner<T, Done>) -> Self {
+        signer
+            .template
+            .h
+            .retain(|h| !h.eq_ignore_ascii_case("ARC-Seal"));
+        signer.template.i.clear();
+        ArcSealer { signer }
+    }
+}
+
+impl<T: SigningKey> ArcSealer<T> {
+    /// Adds the next ARC set to `message`. `results` is the payload of the
+    /// `ARC-Authentication-Results` header, such as `mx.example.org; dkim=pass
+    /// header.d=example.com`, and `cv` is the status of the message's existing
+    /// ARC chain, which is [`ChainValidation::None`] for a message without one.
+    pub fn seal(
+        &self,
+        message: &[u8],
+        results: impl Into<String>,
+        cv: ChainValidation,
+    ) -> crate::Result<ArcSet> {
+        self.seal_with_clock(message, results, cv, &SystemClock)
+    }
+
+    /// Adds the next ARC set to `message`, reading the `t=` and `x=` times
+    /// from `clock`.
+    pub fn seal_with_clock(
+        &self,
+        message: &[u8],
+        results: impl Into<String>,
+        cv: ChainValidation,
+        clock: &impl Clock,
+    ) -> crate::Result<ArcSet> {
+        let now = clock.now();
+        let headers = HeaderIterator::new(message).collect::<Vec<_>>();
+        let sets = arc_sets(&headers)?;
+        let instance = sets.len() as u32 + 1;
+
+        // Sets are only added to chains that have not failed yet
+        if instance > MAX_ARC_SETS {
+            return Err(Error::ArcChainTooLong);
+        }
+        match sets.last() {
+            Some(set) => {
+                let last_seal = set
+                    .seal
+                    .and_then(|(_, value)| ArcSeal::parse(value).ok())
+                    .ok_or(Error::InvalidArcChain)?;
+                if cv == ChainValidation::None || last_seal.cv == ChainValidation::Fail {
+                    return Err(Error::InvalidArcChain);
+                }
+            }
+            None if cv != ChainValidation::None => return Err(Error::InvalidArcChain),
+            None => (),
+        }
+
+        // Sign the message
+        let signer = &self.signer;
+        let (canonical_headers, signed_headers, canonical_body) =
+            signer.template.canonicalize_with(
+                HeaderIterator::new(message),
+                &signer.oversign,
+                signer.auto_headers,
+            )?;
+        let (body_hash, body_len) = canonical_body.hash(HashAlgorithm::from(signer.template.a));
+        let signature = signer.unsigned_signature(
+            &canonical_headers,
+            signed_headers,
+            body_hash,
+            body_len,
+            now,
+        )?;
+
+        let mut set = ArcSet {
+            instance,
+            results: results.into(),
+            signature,
+            seal: ArcSeal {
+                i: instance,
+                a: signer.template.a,
+                b: Vec::new(),
+                cv,
+                d: signer.template.d.clone(),
+                s: signer.template.s.clone(),
+                t: if signer.emit_timestamp { now } else { 0 },
+            },
+        };
+        let b = signer.key.sign(ArcSignableMessage {
+            headers: canonical_headers,
+            set: &set,
+        })?;
+        set.signature.b = BASE64_STANDARD.encode(&b).into_bytes();
+
+        // Seal the chain, including the new results and message signature
+        let mut new_headers = Vec::new();
+        set.write_results(&mut new_headers);
+        set.write_signature(&mut new_headers, true);
+        let b = signer.key.sign(SealableChain {
+            sets: &sets,
+            new_headers: &new_headers,
+            seal: &set.seal,
+        })?;
+        set.seal.b = BASE64_STANDARD.encode(&b).into_bytes();
+
+        Ok(set)
+    }
+}
+
+struct ArcSignableMessage<'a> {
+    headers: CanonicalHeaders<'a>,
+    set: &'a ArcSet,
+}
+
+impl Writable for ArcSignableMessage<'_> {
+    fn write(self, writer: &mut impl Writer) {
+        self.headers.write(writer);
+        self.set.write_signature(writer, false);
+    }
+}
+
+/// The ARC sets covered by a seal, in instance order, followed by the seal
+/// itself without its signature.
+pub(crate) struct SealableChain<'a, 'x> {
+    pub(crate) sets: &'a [ArcSetHeaders<'x>],
+    pub(crate) new_headers: &'a [u8],
+    pub(crate) seal: &'a ArcSeal,
+}
+
+impl Writable for SealableChain<'_, '_> {
+    fn write(self, writer: &mut impl Writer) {
+        Canonicalization::Relaxed.canonicalize_headers(
+            self.sets
+                .iter()
+                .flat_map(ArcSetHeaders::iter)
+                .chain(HeaderIterator::new(self.new_headers)),
+            writer,
+        );
+        self.seal.write(writer, false);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        arc::{ArcSeal, MAX_ARC_SETS},
+        common::crypto::Ed25519Key,
+        ArcResult, ArcSealer, ArcVerifier, ChainValidation, DkimSigner, Error, FixedClock,
+        HeaderWriter, MemoryResolver,
+    };
+
+    const MESSAGE: &str =
+        "From: joe@example.com\r\nTo: jane@example.org\r\nSubject: Hi\r\n\r\nHello\r\n";
+
+    fn key() -> Ed25519Key {
+        Ed25519Key::from_seed(&[1; 32]).unwrap()
+    }
+
+    fn sealer() -> ArcSealer<Ed25519Key> {
+        ArcSealer::new(
+            DkimSigner::from_key(key())
+                .domain("example.org")
+                .selector("arc")
+                .headers(["From", "To", "Subject", "ARC-Seal"]),
+        )
+    }
+
+    fn verifier() -> ArcVerifier<MemoryResolver> {
+        let mut resolver = MemoryResolver::new();
+        resolver.txt_add(
+            "arc._domainkey.example.org",
+            key().public_key_record().to_string(),
+        );
+        ArcVerifier::new(resolver)
+    }
+
+    fn seal(message: &str, cv: ChainValidation) -> crate::Result<String> {
+        sealer()
+            .seal_with_clock(
+                message.as_bytes(),
+                "example.org; dkim=pass header.d=example.com",
+                cv,
+                &FixedClock(1000),
+            )
+            .map(|set| format!("{}{message}", set.to_header()))
+    }
+
+    #[test]
+    fn seal_first_set() {
+        let set = sealer()
+            .seal_with_clock(
+                MESSAGE.as_bytes(),
+                "example.org; dkim=pass header.d=example.com",
+                ChainValidation::None,
+                &FixedClock(1000),
+            )
+            .unwrap();
+        assert_eq!(set.instance, 1);
+        assert_eq!(set.seal.i, 1);
+        assert_eq!(set.seal.cv, ChainValidation::None);
+        assert_eq!(set.seal.t, 1000);
+        assert_eq!(set.signature.h, ["Subject", "To", "From"]);
+
+        let header = set.to_header();
+        let (seal, rest) = header.split_once("\r\nARC-Message-Signature").unwrap();
+        assert!(seal.starts_with("ARC-Seal: i=1; a=ed25519-sha256; t=1000; cv=none;"));
+        assert_eq!(ArcSeal::parse(seal.as_bytes()).unwrap(), set.seal);
+        assert!(rest.starts_with(": i=1; a=ed25519-sha256;"));
+        assert!(rest.ends_with(
+            "ARC-Authentication-Results: i=1; example.org; dkim=pass header.d=example.com\r\n"
+        ));
+
+        let message = format!("{header}{MESSAGE}");
+        assert_eq!(
+            verifier().verify(message.as_bytes()).result,
+            ArcResult::Pass
+        );
+    }
+
+    #[test]
+    fn seal_chain_validation() {
+        // A message without ARC sets can only be sealed with cv=none
+        assert_eq!(
+            seal(MESSAGE, ChainValidation::Pass),
+            Err(Error::InvalidArcChain)
+        );
+        let sealed = seal(MESSAGE, ChainValidation::None).unwrap();
+        assert_eq!(
+            seal(&sealed, ChainValidation::None),
+            Err(Error::InvalidArcChain)
+        );
+
+        // A failed chain is never sealed again
+        let failed = seal(&sealed, ChainValidation::Fail).unwrap();
+        assert_eq!(
+            seal(&failed, ChainValidation::Pass),
+            Err(Error::InvalidArcChain)
+        );
+        assert_eq!(
+            seal(&failed, ChainValidation::Fail),
+            Err(Error::InvalidArcChain)
+        );
+    }
+
+    #[test]
+    fn seal_limit() {
+        let mut message = seal(MESSAGE, ChainValidation::None).unwrap();
+        for _ in 1..MAX_ARC_SETS {
+            message = seal(&message, ChainValidation::Pass).unwrap();
+        }
+        let output = verifier().verify(message.as_bytes());
+        assert_eq!(output.result, ArcResult::Pass);
+        assert_eq!(output.seals.len(), MAX_ARC_SETS as usize);
+
+        assert_eq!(
+            seal(&message, ChainValidation::Pass),
+            Err(Error::ArcChainTooLong)
+        );
+    }
+}
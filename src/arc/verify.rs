@@ -0,0 +1,319 @@
+use super::{
+    parse::{arc_sets, ArcSetHeaders},
+    ArcOutput, ArcResult, ArcSeal, ArcVerifier, ChainValidation,
+};
+use crate::{
+    common::{
+        clock::{Clock, SystemClock},
+        crypto::HashAlgorithm,
+        headers::{HeaderIterator, HeaderStream},
+        resolver::Resolver,
+    },
+    dkim::{
+        verify::{strip_signature, VerifiableMessage},
+        Canonicalization, DkimResult, DkimVerifier, Signature,
+    },
+    Error,
+};
+use base64::{engine::general_purpose::STANDARD as BASE64_STANDARD, Engine as _};
+
+impl<R: Resolver> ArcVerifier<R> {
+    pub fn new(resolver: R) -> Self {
+        ArcVerifier {
+            verifier: DkimVerifier::new(resolver),
+        }
+    }
+
+    /// Validates the ARC chain of `message`: the structure of its sets, the
+    /// `cv=` of every seal, every `ARC-Seal` and the `ARC-Message-Signature`
+    /// of the latest set. Older message signatures only determine
+    /// [`ArcOutput::oldest_pass`].
+    pub fn verify(&self, message: &[u8]) -> ArcOutput {
+        self.verify_with_clock(message, &SystemClock)
+    }
+
+    /// Validates the ARC chain of `message`, checking signature expiration
+    /// against `clock`.
+    pub fn verify_with_clock(&self, message: &[u8], clock: &impl Clock) -> ArcOutput {
+        let now = clock.now();
+        let mut iterator = HeaderIterator::new(message);
+        let headers = (&mut iterator).collect::<Vec<_>>();
+        let body = iterator.body();
+        let mut output = ArcOutput {
+            result: ArcResult::None,
+            seals: Vec::new(),
+            oldest_pass: 0,
+        };
+
+        // Check the structure of the chain
+        let sets = match arc_sets(&headers) {
+            Ok(sets) if sets.is_empty() => return output,
+            Ok(sets) => sets,
+            Err(err) => return output.with_result(ArcResult::Fail(err)),
+        };
+        for (instance, set) in (1..).zip(&sets) {
+            match set.seal.map(|(_, value)| ArcSeal::parse(value)) {
+                Some(Ok(seal)) => output.seals.push(seal),
+                Some(Err(err)) => return output.with_result(ArcResult::Fail(err)),
+                None => return output.with_result(ArcResult::Fail(Error::InvalidArcChain)),
+            }
+            let expected_cv = if instance == 1 {
+                ChainValidation::None
+            } else {
+                ChainValidation::Pass
+            };
+            if output.seals[instance - 1].cv != expected_cv {
+                return output.with_result(ArcResult::Fail(Error::InvalidArcChain));
+            }
+        }
+
+        // Verify the message signatures, newest first
+        for (instance, set) in sets
+            .iter()
+            .enumerate()
+            .rev()
+            .map(|(pos, set)| (pos + 1, set))
+        {
+            let result = match set.signature {
+                Some(header) => self.verify_signature(instance, header, &headers, body, now),
+                None => ArcResult::Fail(Error::InvalidArcChain),
+            };
+            if result != ArcResult::Pass {
+                if instance == sets.len() {
+                    return output.with_result(result);
+                }
+                output.oldest_pass = instance as u32 + 1;
+                break;
+            }
+        }
+
+        // Verify the seals, newest first
+        for instance in (1..=sets.len()).rev() {
+            let result = self.verify_seal(&sets[..instance], &output.seals[instance - 1]);
+            if result != ArcResult::Pass {
+                return output.with_result(result);
+            }
+        }
+
+        output.with_result(ArcResult::Pass)
+    }
+
+    fn verify_signature(
+        &self,
+        instance: usize,
+        (name, value): (&[u8], &[u8]),
+        headers: &[(&[u8], &[u8])],
+        body: &[u8],
+        now: u64,
+    ) -> ArcResult {
+        let signature = match Signature::parse_arc(value) {
+            Ok((signature, i)) if i as usize == instance => signature,
+            Ok(_) => return ArcResult::Fail(Error::InvalidArcChain),
+            Err(err) => return ArcResult::Fail(err),
+        };
+        match self
+            .verifier
+            .verify_parsed(&signature, headers, body, (name, value), now)
+        {
+            DkimResult::Pass => ArcResult::Pass,
+            DkimResult::TempError(err) => ArcResult::TempError(err),
+            DkimResult::Neutral(err) | DkimResult::Fail(err) | DkimResult::PermError(err) => {
+                ArcResult::Fail(err)
+            }
+        }
+    }
+
+    /// Verifies the seal of the last of `sets`, which covers all of them.
+    fn verify_seal(&self, sets: &[ArcSetHeaders<'_>], seal: &ArcSeal) -> ArcResult {
+        let Some((last, previous)) = sets.split_last() else {
+            return ArcResult::Fail(Error::InvalidArcChain);
+        };
+        let Some((name, value)) = last.seal else {
+            return ArcResult::Fail(Error::InvalidArcChain);
+        };
+        let (_, key) = match self.verifier.fetch_key(&seal.s, &seal.d) {
+            Ok(key) => key,
+            Err(err @ Error::DnsError(_)) => return ArcResult::TempError(err),
+            Err(err) => return ArcResult::Fail(err),
+        };
+        let Ok(b) = BASE64_STANDARD.decode(&seal.b) else {
+            return ArcResult::Fail(Error::Base64);
+        };
+
+        let headers_hash = HashAlgorithm::from(seal.a).hash(VerifiableMessage {
+            canonicalization: Canonicalization::Relaxed,
+            headers: previous
+                .iter()
+                .flat_map(ArcSetHeaders::iter)
+                .chain([last.results, last.signature].into_iter().flatten())
+                .collect(),
+            signature_header: (name, strip_signature(value)),
+        });
+
+        match key.verify(headers_hash.as_ref(), &b, seal.a) {
+            Ok(()) => ArcResult::Pass,
+            Err(err) => ArcResult::Fail(err),
+        }
+    }
+}
+
+impl ArcOutput {
+    fn with_result(mut self, result: ArcResult) -> Self {
+        self.result = result;
+        self
+    }
+
+    /// The `cv=` to use when adding an ARC set to the message, see
+    /// [`ArcSealer::seal`](super::ArcSealer::seal).
+    pub fn chain_validation(&self) -> ChainValidation {
+        match self.result {
+            ArcResult::None => ChainValidation::None,
+            ArcResult::Pass => ChainValidation::Pass,
+            ArcResult::Fail(_) | ArcResult::TempError(_) => ChainValidation::Fail,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        common::crypto::{Ed25519Key, RsaKey, Sha256},
+        dkim::Canonicalization,
+        ArcResult, ArcSealer, ArcVerifier, ChainValidation, DkimSigner, Error, HeaderWriter,
+        MemoryResolver,
+    };
+
+    const MESSAGE: &str =
+        "From: joe@example.com\r\nTo: jane@example.org\r\nSubject: Hi\r\n\r\nHello\r\n";
+
+    fn ed25519() -> Ed25519Key {
+        Ed25519Key::from_seed(&[1; 32]).unwrap()
+    }
+
+    fn rsa() -> RsaKey<Sha256> {
+        RsaKey::from_pem(include_str!("../../private_key.pem")).unwrap()
+    }
+
+    fn verifier() -> ArcVerifier<MemoryResolver> {
+        let mut resolver = MemoryResolver::new();
+        resolver.txt_add(
+            "arc._domainkey.one.example",
+            ed25519().public_key_record().to_string(),
+        );
+        resolver.txt_add(
+            "arc._domainkey.two.example",
+            rsa().public_key_record().unwrap().to_string(),
+        );
+        ArcVerifier::new(resolver)
+    }
+
+    /// Seals `message` with the key of `one.example` or `two.example`, using
+    /// the chain validation status reported by the verifier.
+    fn seal(message: &str, domain: &str) -> String {
+        let cv = verifier().verify(message.as_bytes()).chain_validation();
+        let results = format!("{domain}; arc={}", cv.as_str());
+        let set = if domain == "one.example" {
+            ArcSealer::new(
+                DkimSigner::from_key(ed25519())
+                    .domain(domain)
+                    .selector("arc")
+                    .recommended_headers(),
+            )
+            .seal(message.as_bytes(), results, cv)
+        } else {
+            ArcSealer::new(
+                DkimSigner::from_key(rsa())
+                    .domain(domain)
+                    .selector("arc")
+                    .headers(["From", "Subject"])
+                    .header_canonicalization(Canonicalization::Simple),
+            )
+            .seal(message.as_bytes(), results, cv)
+        };
+        format!("{}{message}", set.unwrap().to_header())
+    }
+
+    #[test]
+    fn verify_chain() {
+        let output = verifier().verify(MESSAGE.as_bytes());
+        assert_eq!(output.result, ArcResult::None);
+        assert_eq!(output.chain_validation(), ChainValidation::None);
+
+        let first = seal(MESSAGE, "one.example");
+        let output = verifier().verify(first.as_bytes());
+        assert_eq!(output.result, ArcResult::Pass);
+        assert_eq!(output.seals.len(), 1);
+
+        let second = seal(&first, "two.example");
+        let output = verifier().verify(second.as_bytes());
+        assert_eq!(output.result, ArcResult::Pass);
+        assert_eq!(output.oldest_pass, 0);
+        assert_eq!(
+            output.seals.iter().map(|seal| seal.cv).collect::<Vec<_>>(),
+            [ChainValidation::None, ChainValidation::Pass]
+        );
+        assert!(second.contains("ARC-Authentication-Results: i=2; two.example; arc=pass\r\n"));
+    }
+
+    #[test]
+    fn modified_body() {
+        let second = seal(&seal(MESSAGE, "one.example"), "two.example");
+        let modified = format!("{second}Footer\r\n");
+        assert_eq!(
+            verifier().verify(modified.as_bytes()).result,
+            ArcResult::Fail(Error::FailedBodyHashMatch)
+        );
+
+        // A body modified before the second set is sealed with cv=fail,
+        // which fails the whole chain
+        let first = seal(MESSAGE, "one.example");
+        let second = seal(&format!("{first}Footer\r\n"), "two.example");
+        let output = verifier().verify(second.as_bytes());
+        assert_eq!(output.result, ArcResult::Fail(Error::InvalidArcChain));
+        assert_eq!(output.seals[1].cv, ChainValidation::Fail);
+    }
+
+    #[test]
+    fn modified_headers() {
+        let second = seal(&seal(MESSAGE, "one.example"), "two.example");
+
+        // The newest message signature covers the subject
+        let modified = second.replace("Subject: Hi", "Subject: Hi!");
+        assert_eq!(
+            verifier().verify(modified.as_bytes()).result,
+            ArcResult::Fail(Error::FailedVerification)
+        );
+
+        // Only the oldest message signature covers the recipient
+        let modified = second.replace("To: jane@example.org", "To: john@example.org");
+        let output = verifier().verify(modified.as_bytes());
+        assert_eq!(output.result, ArcResult::Pass);
+        assert_eq!(output.oldest_pass, 2);
+
+        // The seal covers the authentication results of every set
+        let modified = second.replace("two.example; arc=pass", "two.example; arc=fail");
+        assert_eq!(
+            verifier().verify(modified.as_bytes()).result,
+            ArcResult::Fail(Error::FailedVerification)
+        );
+        let removed = second.replace("ARC-Authentication-Results: i=1", "X-Removed: i=1");
+        assert_eq!(
+            verifier().verify(removed.as_bytes()).result,
+            ArcResult::Fail(Error::InvalidArcChain)
+        );
+    }
+
+    #[test]
+    fn resolver_error() {
+        let second = seal(&seal(MESSAGE, "one.example"), "two.example");
+        let mut resolver = MemoryResolver::new();
+        resolver.txt_error(
+            "arc._domainkey.two.example",
+            Error::DnsError("timeout".to_string()),
+        );
+        assert!(matches!(
+            ArcVerifier::new(resolver).verify(second.as_bytes()).result,
+            ArcResult::TempError(_)
+        ));
+    }
+}
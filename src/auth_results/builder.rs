@@ -0,0 +1,173 @@
+use super::{AuthResult, AuthenticationResults, Method, MethodResult, Property};
+use crate::{
+    arc::{ArcOutput, ArcResult},
+    dkim::{DkimOutput, DkimResult},
+    dmarc::{DmarcOutput, DmarcResult},
+    spf::{SpfIdentity, SpfOutput, SpfResult},
+    Error,
+};
+
+impl AuthenticationResults {
+    pub fn new(authserv_id: impl Into<String>) -> Self {
+        AuthenticationResults {
+            authserv_id: authserv_id.into(),
+            results: Vec::new(),
+        }
+    }
+
+    pub fn with_result(mut self, result: MethodResult) -> Self {
+        self.results.push(result);
+        self
+    }
+
+    /// Adds the result of verifying a DKIM signature, identifying the signature
+    /// by its domain, selector, algorithm and the first 8 characters of its
+    /// `b=` tag (RFC 6008).
+    pub fn with_dkim(mut self, output: &DkimOutput) -> Self {
+        let (result, err) = match &output.result {
+            DkimResult::Pass => (AuthResult::Pass, None),
+            DkimResult::Neutral(err) => (AuthResult::Neutral, Some(err)),
+            DkimResult::Fail(err) => (AuthResult::Fail, Some(err)),
+            DkimResult::PermError(err) => (AuthResult::PermError, Some(err)),
+            DkimResult::TempError(err) => (AuthResult::TempError, Some(err)),
+        };
+        let mut method_result = MethodResult::new(Method::Dkim, result);
+        method_result.reason = err.map(Error::to_string);
+        if let Some(signature) = &output.signature {
+            method_result = method_result.property("header", "d", &signature.d);
+            if !signature.i.is_empty() {
+                method_result = method_result.property("header", "i", &signature.i);
+            }
+            method_result = method_result
+                .property("header", "s", &signature.s)
+                .property("header", "a", signature.a.as_str())
+                .property(
+                    "header",
+                    "b",
+                    String::from_utf8_lossy(&signature.b[..signature.b.len().min(8)]),
+                );
+        }
+        self.results.push(method_result);
+        self
+    }
+
+    /// Adds the status of a message's ARC chain, noting the number of ARC sets
+    /// and, when older message signatures no longer validate, the oldest
+    /// instance that does (RFC 8617, section 7.2).
+    pub fn with_arc(mut self, output: &ArcOutput) -> Self {
+        let (result, err) = match &output.result {
+            ArcResult::None => (AuthResult::None, None),
+            ArcResult::Pass => (AuthResult::Pass, None),
+            ArcResult::Fail(err) => (AuthResult::Fail, Some(err)),
+            ArcResult::TempError(err) => (AuthResult::TempError, Some(err)),
+        };
+        let mut method_result = MethodResult::new(Method::Arc, result);
+        method_result.reason = err.map(Error::to_string);
+        if !output.seals.is_empty() {
+            method_result = method_result.comment(format!("i={}", output.seals.len()));
+        }
+        if output.oldest_pass > 0 {
+            method_result =
+                method_result.property("header", "oldest-pass", output.oldest_pass.to_string());
+        }
+        self.results.push(method_result);
+        self
+    }
+
+    /// Adds the result of an SPF check, with the checked identity as the
+    /// `smtp.mailfrom` or `smtp.helo` property and, on failure, the published
+    /// explanation as the reason.
+    pub fn with_spf(mut self, output: &SpfOutput) -> Self {
+        let (result, reason) = match &output.result {
+            SpfResult::None => (AuthResult::None, None),
+            SpfResult::Neutral => (AuthResult::Neutral, None),
+            SpfResult::Pass => (AuthResult::Pass, None),
+            SpfResult::Fail => (AuthResult::Fail, output.explanation.clone()),
+            SpfResult::SoftFail => (AuthResult::SoftFail, None),
+            SpfResult::TempError(err) => (AuthResult::TempError, Some(err.to_string())),
+            SpfResult::PermError(err) => (AuthResult::PermError, Some(err.to_string())),
+        };
+        let mut method_result = MethodResult::new(Method::Spf, result);
+        method_result.reason = reason;
+        self.results.push(match output.identity {
+            SpfIdentity::MailFrom => {
+                method_result.property("smtp", "mailfrom", output.sender.clone())
+            }
+            SpfIdentity::Helo => method_result.property("smtp", "helo", output.domain.clone()),
+        });
+        self
+    }
+
+    /// Adds the result of a DMARC check, with the requested policy and the
+    /// disposition applied in a comment (RFC 7489, section 11.2).
+    pub fn with_dmarc(mut self, output: &DmarcOutput) -> Self {
+        let (result, err) = match &output.result {
+            DmarcResult::None => (AuthResult::None, None),
+            DmarcResult::Pass => (AuthResult::Pass, None),
+            DmarcResult::Fail => (AuthResult::Fail, None),
+            DmarcResult::TempError(err) => (AuthResult::TempError, Some(err)),
+            DmarcResult::PermError(err) => (AuthResult::PermError, Some(err)),
+        };
+        let mut method_result = MethodResult::new(Method::Dmarc, result);
+        method_result.reason = err.map(Error::to_string);
+        if output.record.is_some() {
+            method_result = method_result.comment(format!(
+                "p={} dis={}",
+                output.policy.as_str(),
+                output.disposition.as_str()
+            ));
+        }
+        if !output.domain.is_empty() {
+            method_result = method_result.property("header", "from", output.domain.clone());
+        }
+        self.results.push(method_result);
+        self
+    }
+}
+
+impl MethodResult {
+    pub fn new(method: Method, result: AuthResult) -> Self {
+        MethodResult {
+            method,
+            result,
+            reason: None,
+            comment: None,
+            properties: Vec::new(),
+        }
+    }
+
+    pub fn reason(mut self, reason: impl Into<String>) -> Self {
+        self.reason = Some(reason.into());
+        self
+    }
+
+    pub fn comment(mut self, comment: impl Into<String>) -> Self {
+        self.comment = Some(comment.into());
+        self
+    }
+
+    /// Adds a `ptype.property=value` pair, such as `smtp.mailfrom=user@example.com`.
+    pub fn property(
+        mut self,
+        ptype: impl Into<String>,
+        property: impl Into<String>,
+        value: impl Into<String>,
+    ) -> Self {
+        self.properties.push(Property {
+            ptype: ptype.into(),
+            property: property.into(),
+            value: value.into(),
+        });
+        self
+    }
+
+    /// Returns the value of the `ptype.property` pair, if present.
+    pub fn get_property(&self, ptype: &str, property: &str) -> Option<&str> {
+        self.properties
+            .iter()
+            .find(|p| {
+                p.ptype.eq_ignore_ascii_case(ptype) && p.property.eq_ignore_ascii_case(property)
+            })
+            .map(|p| p.value.as_str())
+    }
+}
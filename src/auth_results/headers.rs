@@ -0,0 +1,329 @@
+use super::{AuthResult, AuthenticationResults, Method, MethodResult};
+use crate::common::headers::{HeaderWriter, Writer};
+use std::fmt::{Display, Formatter};
+
+impl AuthenticationResults {
+    /// Writes the header value, starting each method result on a new line
+    /// and folding long results between their properties.
+    pub fn write(&self, writer: &mut impl Writer, as_header: bool) {
+        let new_line = if as_header {
+            writer.write(b"Authentication-Results: ");
+            &b"\r\n\t"[..]
+        } else {
+            &b" "[..]
+        };
+        writer.write(self.authserv_id.as_bytes());
+
+        if self.results.is_empty() {
+            writer.write(b"; none");
+        }
+        for result in &self.results {
+            writer.write(b";");
+            writer.write(new_line);
+            let mut bw = 1;
+            for (num, token) in result.tokens().into_iter().enumerate() {
+                if num > 0 {
+                    if bw + token.len() >= 76 {
+                        writer.write(new_line);
+                        bw = 1;
+                    } else {
+                        writer.write_len(b" ", &mut bw);
+                    }
+                }
+                writer.write_len(token.as_bytes(), &mut bw);
+            }
+        }
+
+        if as_header {
+            writer.write(b"\r\n");
+        }
+    }
+}
+
+impl MethodResult {
+    /// Splits the result into the tokens that may be separated by a fold.
+    fn tokens(&self) -> Vec<String> {
+        let mut tokens = vec![format!("{}={}", self.method.as_str(), self.result.as_str())];
+        if let Some(comment) = &self.comment {
+            tokens.push(format!("({})", escape(comment, b"()\\")));
+        }
+        if let Some(reason) = &self.reason {
+            tokens.push(format!("reason=\"{}\"", escape(reason, b"\"\\")));
+        }
+        for property in &self.properties {
+            tokens.push(format!(
+                "{}.{}={}",
+                property.ptype,
+                property.property,
+                quote_value(&property.value)
+            ));
+        }
+        tokens
+    }
+}
+
+/// Quotes a property value unless it only contains characters allowed in a
+/// token, a domain name or an address.
+fn quote_value(value: &str) -> String {
+    if !value.is_empty()
+        && value
+            .bytes()
+            .all(|ch| ch.is_ascii_graphic() && !b"()<>,;:\\\"[]?".contains(&ch))
+    {
+        value.to_string()
+    } else {
+        format!("\"{}\"", escape(value, b"\"\\"))
+    }
+}
+
+fn escape(value: &str, specials: &[u8]) -> String {
+    let mut result = String::with_capacity(value.len());
+    for ch in value.chars() {
+        match ch {
+            '\r' | '\n' => continue,
+            _ if ch.is_ascii() && specials.contains(&(ch as u8)) => result.push('\\'),
+            _ => (),
+        }
+        result.push(ch);
+    }
+    result
+}
+
+impl Method {
+    pub fn as_str(&self) -> &str {
+        match self {
+            Method::Dkim => "dkim",
+            Method::Spf => "spf",
+            Method::Dmarc => "dmarc",
+            Method::Arc => "arc",
+            Method::Other(method) => method,
+        }
+    }
+}
+
+impl AuthResult {
+    pub fn as_str(&self) -> &str {
+        match self {
+            AuthResult::Pass => "pass",
+            AuthResult::Fail => "fail",
+            AuthResult::SoftFail => "softfail",
+            AuthResult::Neutral => "neutral",
+            AuthResult::None => "none",
+            AuthResult::TempError => "temperror",
+            AuthResult::PermError => "permerror",
+            AuthResult::Policy => "policy",
+            AuthResult::Other(result) => result,
+        }
+    }
+}
+
+impl HeaderWriter for AuthenticationResults {
+    fn write_header(&self, writer: &mut impl Writer) {
+        self.write(writer, true);
+    }
+}
+
+/// Renders the header value on a single line, as used for the payload of an
+/// `ARC-Authentication-Results` header.
+impl Display for AuthenticationResults {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let mut buf = Vec::new();
+        self.write(&mut buf, false);
+        f.write_str(&String::from_utf8_lossy(&buf))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        common::crypto::Ed25519Key, AuthResult, AuthenticationResults, ChainValidation, DkimSigner,
+        DkimVerifier, DmarcVerifier, HeaderWriter, MemoryResolver, Method, MethodResult,
+        SpfVerifier,
+    };
+
+    fn assert_round_trip(results: &AuthenticationResults) {
+        let header = results.to_header();
+        assert!(
+            header.split("\r\n").all(|line| line.len() <= 78),
+            "{header}"
+        );
+        assert_eq!(
+            &AuthenticationResults::parse(header.as_bytes()).unwrap(),
+            results
+        );
+        assert_eq!(
+            &results
+                .to_string()
+                .parse::<AuthenticationResults>()
+                .unwrap(),
+            results
+        );
+    }
+
+    #[test]
+    fn write_results() {
+        let results =
+            AuthenticationResults::new("mx.example.org")
+                .with_result(
+                    MethodResult::new(Method::Dkim, AuthResult::Fail)
+                        .reason("bad \"signature\"")
+                        .comment("checked (twice)")
+                        .property("header", "d", "example.com"),
+                )
+                .with_result(
+                    MethodResult::new(Method::Other("iprev".to_string()), AuthResult::Pass)
+                        .property("policy", "iprev", "192.0.2.1 (mail.example.com); x"),
+                );
+        assert_eq!(
+            results.to_header(),
+            concat!(
+                "Authentication-Results: mx.example.org;\r\n",
+                "\tdkim=fail (checked \\(twice\\)) reason=\"bad \\\"signature\\\"\"\r\n",
+                "\theader.d=example.com;\r\n",
+                "\tiprev=pass policy.iprev=\"192.0.2.1 (mail.example.com); x\"\r\n"
+            )
+        );
+        assert_eq!(
+            results.to_string(),
+            concat!(
+                "mx.example.org; dkim=fail (checked \\(twice\\)) ",
+                "reason=\"bad \\\"signature\\\"\" header.d=example.com; ",
+                "iprev=pass policy.iprev=\"192.0.2.1 (mail.example.com); x\""
+            )
+        );
+        assert_round_trip(&results);
+
+        let results = AuthenticationResults::new("mx.example.org");
+        assert_eq!(
+            results.to_header(),
+            "Authentication-Results: mx.example.org; none\r\n"
+        );
+        assert_round_trip(&results);
+    }
+
+    #[test]
+    fn dkim_arc_round_trip() {
+        let message = "From: joe@example.com\r\nSubject: Hi\r\n\r\nHello\r\n";
+        let key = Ed25519Key::from_seed(&[1; 32]).unwrap();
+        let mut resolver = MemoryResolver::new();
+        resolver.txt_add(
+            "ed._domainkey.example.com",
+            key.public_key_record().to_string(),
+        );
+        let signature = DkimSigner::from_key(key)
+            .domain("example.com")
+            .selector("ed")
+            .headers(["From", "Subject"])
+            .agent_user_identifier("joe@example.com")
+            .sign(message.as_bytes())
+            .unwrap();
+        let signed = format!("{}{message}", signature.to_header());
+        let pass = DkimVerifier::new(&resolver).verify(signed.as_bytes());
+        let fail = DkimVerifier::new(&resolver).verify(format!("{signed}Footer\r\n").as_bytes());
+        let arc = crate::ArcVerifier::new(&resolver).verify(signed.as_bytes());
+
+        let results = AuthenticationResults::new("mx.example.org")
+            .with_dkim(&pass[0])
+            .with_dkim(&fail[0])
+            .with_arc(&arc);
+        assert_eq!(results.results.len(), 3);
+        assert_eq!(results.results[0].result, AuthResult::Pass);
+        assert_eq!(
+            results.results[0].get_property("header", "i"),
+            Some("joe@example.com")
+        );
+        assert_eq!(
+            results.results[0].get_property("header", "b"),
+            Some(&String::from_utf8_lossy(&signature.b[..8])[..])
+        );
+        assert_eq!(results.results[1].result, AuthResult::Fail);
+        assert!(results.results[1].reason.is_some());
+        assert_eq!(results.results[2].method, Method::Arc);
+        assert_eq!(results.results[2].result, AuthResult::None);
+        assert_round_trip(&results);
+
+        let set = crate::ArcSealer::new(
+            DkimSigner::from_key(Ed25519Key::from_seed(&[1; 32]).unwrap())
+                .domain("example.com")
+                .selector("ed")
+                .headers(["From"]),
+        )
+        .seal(
+            signed.as_bytes(),
+            results.to_string(),
+            ChainValidation::None,
+        )
+        .unwrap();
+        let sealed = format!("{}{signed}", set.to_header());
+        let arc = crate::ArcVerifier::new(&resolver).verify(sealed.as_bytes());
+        let results = AuthenticationResults::new("mx.example.net").with_arc(&arc);
+        assert_eq!(results.to_string(), "mx.example.net; arc=pass (i=1)");
+        assert_round_trip(&results);
+    }
+
+    #[test]
+    fn spf_round_trip() {
+        let mut resolver = MemoryResolver::new();
+        resolver.txt_add(
+            "example.com",
+            "v=spf1 ip4:192.0.2.0/24 -all exp=explain.example.com",
+        );
+        resolver.txt_add("explain.example.com", "%{i} is not allowed to send as %{s}");
+        resolver.txt_add("error.example", "v=spf1 ip4:192.0.2.0/33 -all");
+        let verifier = SpfVerifier::new(resolver);
+        let ip = "192.0.2.1".parse().unwrap();
+        let other_ip = "198.51.100.1".parse().unwrap();
+
+        let results = AuthenticationResults::new("mx.example.org")
+            .with_spf(&verifier.verify_mail_from(ip, "mail.example.com", "bill@example.com"))
+            .with_spf(&verifier.verify_mail_from(other_ip, "mail.example.com", "bill@example.com"))
+            .with_spf(&verifier.verify_helo(ip, "example.com"))
+            .with_spf(&verifier.verify_mail_from(ip, "mail.example.com", "joe@error.example"));
+        assert_eq!(
+            results.to_string(),
+            concat!(
+                "mx.example.org; spf=pass smtp.mailfrom=bill@example.com; ",
+                "spf=fail reason=\"198.51.100.1 is not allowed to send as bill@example.com\" ",
+                "smtp.mailfrom=bill@example.com; ",
+                "spf=pass smtp.helo=example.com; ",
+                "spf=permerror reason=\"Invalid SPF record term 'ip4:192.0.2.0/33'\" ",
+                "smtp.mailfrom=joe@error.example"
+            )
+        );
+        assert_round_trip(&results);
+    }
+
+    #[test]
+    fn dmarc_round_trip() {
+        let mut resolver = MemoryResolver::new();
+        resolver.txt_add("_dmarc.example.com", "v=DMARC1; p=reject; sp=quarantine");
+        resolver.txt_add("example.com", "v=spf1 ip4:192.0.2.0/24 -all");
+        let spf = SpfVerifier::new(&resolver).verify_mail_from(
+            "192.0.2.1".parse().unwrap(),
+            "mail.example.com",
+            "bill@example.com",
+        );
+        let unaligned = SpfVerifier::new(&resolver).verify_mail_from(
+            "192.0.2.1".parse().unwrap(),
+            "mail.example.net",
+            "bill@example.net",
+        );
+        let verifier = DmarcVerifier::new(&resolver);
+
+        let results = AuthenticationResults::new("mx.example.org")
+            .with_dmarc(&verifier.verify(b"From: joe@example.com\r\n\r\n", &[], &spf))
+            .with_dmarc(&verifier.verify(b"From: joe@mail.example.com\r\n\r\n", &[], &unaligned))
+            .with_dmarc(&verifier.verify(b"From: joe@example.org\r\n\r\n", &[], &spf))
+            .with_dmarc(&verifier.verify(b"Subject: Hi\r\n\r\n", &[], &spf));
+        assert_eq!(
+            results.to_string(),
+            concat!(
+                "mx.example.org; dmarc=pass (p=reject dis=none) header.from=example.com; ",
+                "dmarc=fail (p=quarantine dis=quarantine) header.from=mail.example.com; ",
+                "dmarc=none header.from=example.org; ",
+                "dmarc=permerror reason=\"Message has no From header\""
+            )
+        );
+        assert_round_trip(&results);
+    }
+}
@@ -0,0 +1,55 @@
+pub mod builder;
+pub mod headers;
+pub mod parse;
+
+/// An `Authentication-Results` header (RFC 8601), recording the outcome of the
+/// authentication methods evaluated by the service named `authserv_id`.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct AuthenticationResults {
+    /// The authentication service identifier, usually the host name of the MTA.
+    pub authserv_id: String,
+    pub results: Vec<MethodResult>,
+}
+
+/// The result of one authentication method, such as `dkim=pass header.d=example.com`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MethodResult {
+    pub method: Method,
+    pub result: AuthResult,
+    /// The `reason=` explaining the result.
+    pub reason: Option<String>,
+    /// A comment following the result, without its parentheses.
+    pub comment: Option<String>,
+    pub properties: Vec<Property>,
+}
+
+/// A `ptype.property=value` pair, such as `header.d=example.com`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Property {
+    /// `smtp`, `header`, `body` or `policy`.
+    pub ptype: String,
+    pub property: String,
+    pub value: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Method {
+    Dkim,
+    Spf,
+    Dmarc,
+    Arc,
+    Other(String),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum AuthResult {
+    Pass,
+    Fail,
+    SoftFail,
+    Neutral,
+    None,
+    TempError,
+    PermError,
+    Policy,
+    Other(String),
+}
@@ -0,0 +1,409 @@
+use super::{AuthResult, AuthenticationResults, Method, MethodResult, Property};
+use crate::{dkim::parse::strip_field_name, Error};
+use std::str::FromStr;
+
+impl AuthenticationResults {
+    /// Parses an `Authentication-Results` header, either the value alone or the
+    /// complete header including its field name. The value of an
+    /// `ARC-Authentication-Results` header is accepted as well, ignoring its
+    /// `i=` tag.
+    ///
+    /// Parsing is lenient since these headers are written by many different
+    /// MTAs: unknown methods and result values are kept as
+    /// [`Method::Other`] and [`AuthResult::Other`], and malformed method
+    /// results or properties are skipped. Only a missing authentication service
+    /// identifier is an error.
+    pub fn parse(header: &[u8]) -> crate::Result<Self> {
+        let header = strip_field_name(header, b"Authentication-Results");
+        let header = strip_field_name(header, b"ARC-Authentication-Results");
+        let header = String::from_utf8_lossy(header);
+        let mut statements = tokenize(&header).into_iter();
+
+        // The first statement holds the authentication service identifier,
+        // optionally preceded by the instance of an ARC set
+        let mut authserv_id = None;
+        for statement in statements.by_ref() {
+            authserv_id = statement.into_iter().find_map(|token| match token {
+                Token::Word(word) | Token::Quoted(word) => Some(word),
+                Token::Comment(_) => None,
+            });
+            if !authserv_id
+                .as_deref()
+                .is_some_and(|id| id.starts_with("i=") || id.starts_with("I="))
+            {
+                break;
+            }
+        }
+        let mut results = AuthenticationResults::new(
+            authserv_id
+                .filter(|id| !id.contains('='))
+                .ok_or_else(|| Error::InvalidTag("authserv-id".to_string()))?,
+        );
+
+        results.results = statements.filter_map(parse_method_result).collect();
+        Ok(results)
+    }
+}
+
+impl FromStr for AuthenticationResults {
+    type Err = Error;
+
+    fn from_str(s: &str) -> crate::Result<Self> {
+        AuthenticationResults::parse(s.as_bytes())
+    }
+}
+
+fn parse_method_result(statement: Vec<Token>) -> Option<MethodResult> {
+    let mut tokens = statement.into_iter().peekable();
+    let mut comments = Vec::new();
+    let mut pairs = Vec::new();
+
+    while let Some(token) = tokens.next() {
+        let word = match token {
+            Token::Word(word) => word,
+            Token::Comment(comment) => {
+                comments.push(comment);
+                continue;
+            }
+            Token::Quoted(_) => continue,
+        };
+
+        // Accept `name=value`, `name= value` and `name = value`
+        let (name, value) = match word.split_once('=') {
+            Some((name, value)) => (name.to_string(), value.to_string()),
+            None if tokens
+                .peek()
+                .is_some_and(|next| matches!(next, Token::Word(next) if next.starts_with('='))) =>
+            {
+                let Some(Token::Word(next)) = tokens.next() else {
+                    continue;
+                };
+                (word, next[1..].to_string())
+            }
+            None => continue,
+        };
+        let value = if value.is_empty() {
+            match tokens.next_if(|next| !matches!(next, Token::Comment(_))) {
+                Some(Token::Word(value) | Token::Quoted(value)) => value,
+                _ => continue,
+            }
+        } else {
+            value
+        };
+        pairs.push((name, value));
+    }
+
+    let mut pairs = pairs.into_iter();
+    let (method, result) = pairs.next()?;
+    // Drop the method version, as in `dkim/1=pass`
+    let method = method.split('/').next().unwrap_or_default().trim();
+    if method.is_empty() || method.contains('.') {
+        return None;
+    }
+
+    let mut method_result = MethodResult::new(Method::parse(method), AuthResult::parse(&result));
+    method_result.comment = comments.into_iter().next();
+    for (name, value) in pairs {
+        if name.eq_ignore_ascii_case("reason") {
+            method_result.reason = Some(value);
+        } else if let Some((ptype, property)) = name.split_once('.') {
+            method_result.properties.push(Property {
+                ptype: ptype.to_ascii_lowercase(),
+                property: property.to_string(),
+                value,
+            });
+        }
+    }
+    Some(method_result)
+}
+
+impl Method {
+    pub fn parse(method: &str) -> Self {
+        match method.to_ascii_lowercase().as_str() {
+            "dkim" => Method::Dkim,
+            "spf" => Method::Spf,
+            "dmarc" => Method::Dmarc,
+            "arc" => Method::Arc,
+            method => Method::Other(method.to_string()),
+        }
+    }
+}
+
+impl AuthResult {
+    pub fn parse(result: &str) -> Self {
+        match result.to_ascii_lowercase().as_str() {
+            "pass" => AuthResult::Pass,
+            "fail" | "hardfail" => AuthResult::Fail,
+            "softfail" => AuthResult::SoftFail,
+            "neutral" => AuthResult::Neutral,
+            "none" => AuthResult::None,
+            "temperror" | "temperr" => AuthResult::TempError,
+            "permerror" | "permerr" => AuthResult::PermError,
+            "policy" => AuthResult::Policy,
+            result => AuthResult::Other(result.to_string()),
+        }
+    }
+}
+
+enum Token {
+    Word(String),
+    Quoted(String),
+    Comment(String),
+}
+
+/// Splits a header value into its `;` separated statements, turning quoted
+/// strings and comments into single tokens.
+fn tokenize(value: &str) -> Vec<Vec<Token>> {
+    let mut statements = vec![Vec::new()];
+    let mut chars = value.chars().peekable();
+
+    while let Some(ch) = chars.next() {
+        match ch {
+            ';' => statements.push(Vec::new()),
+            '"' => {
+                let mut quoted = String::new();
+                while let Some(ch) = chars.next() {
+                    match ch {
+                        '"' => break,
+                        '\\' => quoted.extend(chars.next()),
+                        '\r' | '\n' => (),
+                        _ => quoted.push(ch),
+                    }
+                }
+                push_token(&mut statements, Token::Quoted(quoted));
+            }
+            '(' => {
+                let mut comment = String::new();
+                let mut depth = 1;
+                while let Some(ch) = chars.next() {
+                    match ch {
+                        '(' => depth += 1,
+                        ')' => {
+                            depth -= 1;
+                            if depth == 0 {
+                                break;
+                            }
+                        }
+                        '\\' => {
+                            comment.extend(chars.next());
+                            continue;
+                        }
+                        _ => (),
+                    }
+                    if !matches!(ch, '\r' | '\n') {
+                        comment.push(ch);
+                    }
+                }
+                push_token(&mut statements, Token::Comment(comment.trim().to_string()));
+            }
+            _ if ch.is_whitespace() => (),
+            _ => {
+                let mut word = String::from(ch);
+                while let Some(&ch) = chars.peek() {
+                    if ch.is_whitespace() || matches!(ch, ';' | '"' | '(') {
+                        // A quoted value directly following `name=` stays a separate token
+                        break;
+                    }
+                    word.push(ch);
+                    chars.next();
+                }
+                push_token(&mut statements, Token::Word(word));
+            }
+        }
+    }
+
+    statements
+}
+
+fn push_token(statements: &mut [Vec<Token>], token: Token) {
+    if let Some(statement) = statements.last_mut() {
+        statement.push(token);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        auth_results::Property, AuthResult, AuthenticationResults, Error, Method, MethodResult,
+    };
+
+    fn property(ptype: &str, property: &str, value: &str) -> Property {
+        Property {
+            ptype: ptype.to_string(),
+            property: property.to_string(),
+            value: value.to_string(),
+        }
+    }
+
+    #[test]
+    fn parse_gmail() {
+        let results = AuthenticationResults::parse(
+            concat!(
+                "Authentication-Results: mx.google.com;\r\n",
+                "       dkim=pass header.i=@example.com header.s=20230601 header.b=ABcd/+1=;\r\n",
+                "       spf=pass (google.com: domain of bounce@example.com designates\r\n",
+                "       209.85.220.41 as permitted sender) smtp.mailfrom=bounce@example.com;\r\n",
+                "       dmarc=pass (p=REJECT sp=REJECT dis=NONE) header.from=example.com\r\n"
+            )
+            .as_bytes(),
+        )
+        .unwrap();
+        assert_eq!(results.authserv_id, "mx.google.com");
+        assert_eq!(
+            results.results,
+            [
+                MethodResult::new(Method::Dkim, AuthResult::Pass)
+                    .property("header", "i", "@example.com")
+                    .property("header", "s", "20230601")
+                    .property("header", "b", "ABcd/+1="),
+                MethodResult::new(Method::Spf, AuthResult::Pass)
+                    .comment(concat!(
+                        "google.com: domain of bounce@example.com designates",
+                        "       209.85.220.41 as permitted sender"
+                    ))
+                    .property("smtp", "mailfrom", "bounce@example.com"),
+                MethodResult::new(Method::Dmarc, AuthResult::Pass)
+                    .comment("p=REJECT sp=REJECT dis=NONE")
+                    .property("header", "from", "example.com"),
+            ]
+        );
+    }
+
+    #[test]
+    fn parse_rfc8601_examples() {
+        // RFC 8601, appendix B.4, with a nested comment and spaces around `=`
+        let results = AuthenticationResults::parse(
+            concat!(
+                "Authentication-Results: example.com;\r\n",
+                "    spf = pass (checked (twice) by spf\\) filter) smtp.mailfrom = example.net;\r\n",
+                "    sender-id=fail header.from=example.com;\r\n",
+                "    dkim/1=pass (good signature) header.d=example.com\r\n"
+            )
+            .as_bytes(),
+        )
+        .unwrap();
+        assert_eq!(results.authserv_id, "example.com");
+        assert_eq!(results.results.len(), 3);
+        assert_eq!(results.results[0].method, Method::Spf);
+        assert_eq!(results.results[0].result, AuthResult::Pass);
+        assert_eq!(
+            results.results[0].comment.as_deref(),
+            Some("checked (twice) by spf) filter")
+        );
+        assert_eq!(
+            results.results[0].properties,
+            [property("smtp", "mailfrom", "example.net")]
+        );
+        assert_eq!(
+            results.results[1].method,
+            Method::Other("sender-id".to_string())
+        );
+        assert_eq!(results.results[1].result, AuthResult::Fail);
+        assert_eq!(results.results[2].method, Method::Dkim);
+        assert_eq!(
+            results.results[2].comment.as_deref(),
+            Some("good signature")
+        );
+        assert_eq!(
+            results.results[2].get_property("header", "d"),
+            Some("example.com")
+        );
+    }
+
+    #[test]
+    fn parse_quoted_values() {
+        let results = AuthenticationResults::parse(
+            concat!(
+                "mx.example.org 1; dkim=fail reason=\"signature \\\"b\\\"; did not verify\" ",
+                "header.d=example.com header.b=\"AbC+/12=\"; ",
+                "spf=hardfail smtp.helo=\"mail example\" (not permitted)"
+            )
+            .as_bytes(),
+        )
+        .unwrap();
+        assert_eq!(results.authserv_id, "mx.example.org");
+        assert_eq!(
+            results.results,
+            [
+                MethodResult::new(Method::Dkim, AuthResult::Fail)
+                    .reason("signature \"b\"; did not verify")
+                    .property("header", "d", "example.com")
+                    .property("header", "b", "AbC+/12="),
+                MethodResult::new(Method::Spf, AuthResult::Fail)
+                    .comment("not permitted")
+                    .property("smtp", "helo", "mail example"),
+            ]
+        );
+    }
+
+    #[test]
+    fn parse_arc_results() {
+        let results = AuthenticationResults::parse(
+            b"ARC-Authentication-Results: i=1; lists.example.org;\r\n\tarc=none;\r\n\tdkim=pass header.d=example.com",
+        )
+        .unwrap();
+        assert_eq!(results.authserv_id, "lists.example.org");
+        assert_eq!(
+            results
+                .results
+                .iter()
+                .map(|result| (&result.method, &result.result))
+                .collect::<Vec<_>>(),
+            [
+                (&Method::Arc, &AuthResult::None),
+                (&Method::Dkim, &AuthResult::Pass)
+            ]
+        );
+    }
+
+    #[test]
+    fn parse_none() {
+        for header in [
+            "example.org; none",
+            "example.org 1; none",
+            "Authentication-Results: example.org (no checks);\r\n none\r\n",
+        ] {
+            let results = AuthenticationResults::parse(header.as_bytes()).unwrap();
+            assert_eq!(
+                results,
+                AuthenticationResults::new("example.org"),
+                "{header}"
+            );
+        }
+    }
+
+    #[test]
+    fn parse_lenient() {
+        // Malformed method results and properties are skipped
+        let results = AuthenticationResults::parse(
+            b"example.org; junk; =pass; header.d=example.com; dkim=pass header.d; spf=pass x.y=z",
+        )
+        .unwrap();
+        assert_eq!(
+            results.results,
+            [
+                MethodResult::new(Method::Dkim, AuthResult::Pass),
+                MethodResult::new(Method::Spf, AuthResult::Pass).property("x", "y", "z"),
+            ]
+        );
+    }
+
+    #[test]
+    fn parse_missing_authserv_id() {
+        for header in [
+            "",
+            "Authentication-Results: ",
+            "; dkim=pass",
+            "i=1; dkim=pass",
+            // Exchange Online omits the authentication service identifier
+            "spf=pass (sender IP is 192.0.2.1) smtp.mailfrom=example.com; dkim=none",
+            "(comment only); dkim=pass",
+        ] {
+            assert_eq!(
+                AuthenticationResults::parse(header.as_bytes()),
+                Err(Error::InvalidTag("authserv-id".to_string())),
+                "{header}"
+            );
+        }
+    }
+}
@@ -1,4 +1,5 @@
 use std::{
+    io,
     iter::{Enumerate, Peekable},
     slice::Iter,
 };
@@ -54,7 +55,8 @@ impl<'x> Iterator for HeaderIterator<'x> {
                         colon_pos = pos;
                     }
                     b'\n' => {
-                        if last_ch == b'\r' || self.start_pos == pos {
+                        if self.start_pos == pos || (last_ch == b'\r' && self.start_pos + 1 == pos)
+                        {
                             return None; // End of headers
                         } else if self
                             .iter
@@ -95,10 +97,20 @@ impl<'x> Iterator for HeaderIterator<'x> {
 
 pub trait HeaderWriter: Sized {
     fn write_header(&self, writer: &mut impl Writer);
+
+    /// Renders the header, replacing any invalid UTF-8 sequences.
     fn to_header(&self) -> String {
         let mut buf = Vec::new();
         self.write_header(&mut buf);
-        String::from_utf8(buf).unwrap()
+        String::from_utf8(buf)
+            .unwrap_or_else(|err| String::from_utf8_lossy(err.as_bytes()).into_owned())
+    }
+
+    /// Renders the header, failing if it is not valid UTF-8.
+    fn try_to_header(&self) -> crate::Result<String> {
+        let mut buf = Vec::new();
+        self.write_header(&mut buf);
+        String::from_utf8(buf).map_err(|_| crate::Error::NonUtf8Header)
     }
 }
 
@@ -125,3 +137,38 @@ impl Writer for Vec<u8> {
         self.extend(buf);
     }
 }
+
+/// Adapts any [`io::Write`], such as a file or socket, into a [`Writer`].
+///
+/// [`Writer::write`] cannot fail, so the first I/O error is kept, later writes
+/// are skipped, and the error is returned by [`IoWriter::finish`].
+#[derive(Debug)]
+pub struct IoWriter<W> {
+    inner: W,
+    error: Option<io::Error>,
+}
+
+impl<W: io::Write> IoWriter<W> {
+    pub fn new(inner: W) -> Self {
+        IoWriter { inner, error: None }
+    }
+
+    /// Flushes the underlying writer and returns it, or the first error
+    /// encountered while writing.
+    pub fn finish(mut self) -> io::Result<W> {
+        match self.error.take() {
+            Some(err) => Err(err),
+            None => self.inner.flush().map(|_| self.inner),
+        }
+    }
+}
+
+impl<W: io::Write> Writer for IoWriter<W> {
+    fn write(&mut self, buf: &[u8]) {
+        if self.error.is_none() {
+            if let Err(err) = self.inner.write_all(buf) {
+                self.error = Some(err);
+            }
+        }
+    }
+}
@@ -1,2 +1,6 @@
+pub mod clock;
 pub mod crypto;
 pub mod headers;
+pub(crate) mod parse;
+pub mod remote;
+pub mod resolver;
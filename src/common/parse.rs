@@ -0,0 +1,155 @@
+use crate::{Error, Result};
+
+/// Iterator over the `tag=value` pairs of an RFC 6376 tag-list, as used by
+/// `DKIM-Signature` headers and DKIM key records.
+pub(crate) struct TagList<'x> {
+    value: &'x [u8],
+}
+
+impl<'x> TagList<'x> {
+    pub fn new(value: &'x [u8]) -> Self {
+        TagList { value }
+    }
+}
+
+impl<'x> Iterator for TagList<'x> {
+    type Item = Result<(&'x [u8], &'x [u8])>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if self.value.is_empty() {
+                return None;
+            }
+            let (spec, rest) = match self.value.iter().position(|&ch| ch == b';') {
+                Some(pos) => (&self.value[..pos], &self.value[pos + 1..]),
+                None => (self.value, &b""[..]),
+            };
+            self.value = rest;
+
+            let spec = spec.trim_ascii();
+            if spec.is_empty() {
+                continue;
+            }
+            return Some(match spec.iter().position(|&ch| ch == b'=') {
+                Some(pos) => {
+                    let name = spec[..pos].trim_ascii();
+                    if is_valid_tag_name(name) {
+                        Ok((name, spec[pos + 1..].trim_ascii()))
+                    } else {
+                        Err(Error::InvalidTag(
+                            String::from_utf8_lossy(name).into_owned(),
+                        ))
+                    }
+                }
+                None => Err(Error::InvalidTag(
+                    String::from_utf8_lossy(spec).into_owned(),
+                )),
+            });
+        }
+    }
+}
+
+// tag-name = ALPHA *ALNUMPUNC
+fn is_valid_tag_name(name: &[u8]) -> bool {
+    name.first().is_some_and(|ch| ch.is_ascii_alphabetic())
+        && name
+            .iter()
+            .all(|&ch| ch.is_ascii_alphanumeric() || ch == b'_')
+}
+
+/// Removes all folding whitespace from a tag value, such as base64 data.
+pub(crate) fn strip_fws(value: &[u8]) -> Vec<u8> {
+    value
+        .iter()
+        .filter(|ch| !ch.is_ascii_whitespace())
+        .copied()
+        .collect()
+}
+
+/// Splits a tag value on `separator`, trimming folding whitespace around each item.
+pub(crate) fn split_list(value: &[u8], separator: u8) -> impl Iterator<Item = &[u8]> {
+    value
+        .split(move |&ch| ch == separator)
+        .map(|item| item.trim_ascii())
+}
+
+/// Decodes a DKIM-quoted-printable value (RFC 6376, section 2.11), ignoring
+/// folding whitespace. Returns `None` on a malformed escape sequence.
+pub(crate) fn decode_dkim_quoted_printable(value: &[u8]) -> Option<Vec<u8>> {
+    let mut result = Vec::with_capacity(value.len());
+    let mut iter = value.iter().filter(|ch| !ch.is_ascii_whitespace());
+
+    while let Some(&ch) = iter.next() {
+        if ch == b'=' {
+            let hi = (*iter.next()? as char).to_digit(16)?;
+            let lo = (*iter.next()? as char).to_digit(16)?;
+            result.push((hi << 4 | lo) as u8);
+        } else {
+            result.push(ch);
+        }
+    }
+
+    Some(result)
+}
+
+/// Decodes a quoted-printable `qp-section` (RFC 6376, section 2.11), as used by
+/// the `n=` tag of key records. Unlike DKIM-quoted-printable, spaces and tabs
+/// are part of the value and only line breaks are dropped. Returns `None` on
+/// a malformed escape sequence.
+pub(crate) fn decode_qp_section(value: &[u8]) -> Option<Vec<u8>> {
+    let mut result = Vec::with_capacity(value.len());
+    let mut iter = value.iter().filter(|&&ch| ch != b'\r' && ch != b'\n');
+
+    while let Some(&ch) = iter.next() {
+        if ch == b'=' {
+            let hi = (*iter.next()? as char).to_digit(16)?;
+            let lo = (*iter.next()? as char).to_digit(16)?;
+            result.push((hi << 4 | lo) as u8);
+        } else {
+            result.push(ch);
+        }
+    }
+
+    Some(result)
+}
+
+/// Extracts the first address from an address header value such as
+/// `"Joe" <joe@Example.COM> (comment)`, returning its local part and its
+/// lowercased domain.
+pub(crate) fn parse_address(value: &[u8]) -> Option<(String, String)> {
+    let mut address = Vec::new();
+    let mut angle_address = None;
+    let mut comment_depth = 0;
+    let mut in_quotes = false;
+    let mut escaped = false;
+
+    for &ch in value {
+        if escaped {
+            escaped = false;
+            if comment_depth == 0 {
+                address.push(ch);
+            }
+            continue;
+        }
+        match ch {
+            b'\\' => escaped = true,
+            b'(' if !in_quotes => comment_depth += 1,
+            b')' if !in_quotes && comment_depth > 0 => comment_depth -= 1,
+            _ if comment_depth > 0 => (),
+            b'"' => in_quotes = !in_quotes,
+            b'<' if !in_quotes => address.clear(),
+            b'>' if !in_quotes => {
+                angle_address = Some(std::mem::take(&mut address));
+                break;
+            }
+            b',' if !in_quotes => break,
+            _ if !ch.is_ascii_whitespace() => address.push(ch),
+            _ => (),
+        }
+    }
+
+    let address = String::from_utf8(angle_address.unwrap_or(address)).ok()?;
+    let (local_part, domain) = address.rsplit_once('@')?;
+    let domain = domain.trim_end_matches('.').to_ascii_lowercase();
+    (!local_part.is_empty() && !domain.is_empty()).then(|| (local_part.to_string(), domain))
+}
@@ -0,0 +1,157 @@
+use super::{
+    crypto::{Algorithm, HashAlgorithm, RawSigner, Sha256, SigningKey},
+    headers::Writable,
+};
+use crate::Result;
+#[cfg(any(test, feature = "test-util"))]
+use std::{
+    sync::mpsc::{self, Sender},
+    thread,
+};
+
+/// A [`SigningKey`] backed by a [`RawSigner`]. Messages are hashed locally and
+/// only their digest is handed to the signer, so the private key never has to
+/// be loaded into the process.
+#[derive(Debug, Clone)]
+pub struct ExternalKey<S> {
+    signer: S,
+}
+
+impl<S: RawSigner> ExternalKey<S> {
+    pub fn new(signer: S) -> Self {
+        ExternalKey { signer }
+    }
+}
+
+impl<S: RawSigner> SigningKey for ExternalKey<S> {
+    type Hasher = Sha256;
+
+    fn sign(&self, input: impl Writable) -> Result<Vec<u8>> {
+        let algorithm = self.signer.algorithm();
+        let hash = HashAlgorithm::from(algorithm).hash(input);
+        self.signer.sign_digest(hash.as_ref(), algorithm)
+    }
+
+    fn algorithm(&self) -> Algorithm {
+        self.signer.algorithm()
+    }
+}
+
+/// An in-process signing service that owns a key on its own thread and signs
+/// the digests sent to it. It stands in for a remote signer in tests; the
+/// thread exits once every handle to the service has been dropped.
+///
+/// Only available with the `test-util` feature.
+#[cfg(any(test, feature = "test-util"))]
+#[derive(Debug, Clone)]
+pub struct SigningService {
+    requests: Sender<SignRequest>,
+    algorithm: Algorithm,
+}
+
+#[cfg(any(test, feature = "test-util"))]
+struct SignRequest {
+    digest: Vec<u8>,
+    algorithm: Algorithm,
+    reply: Sender<Result<Vec<u8>>>,
+}
+
+#[cfg(any(test, feature = "test-util"))]
+impl SigningService {
+    pub fn spawn(key: impl RawSigner + Send + 'static) -> Self {
+        let algorithm = key.algorithm();
+        let (requests, receiver) = mpsc::channel::<SignRequest>();
+        thread::spawn(move || {
+            for request in receiver {
+                let _ = request
+                    .reply
+                    .send(key.sign_digest(&request.digest, request.algorithm));
+            }
+        });
+        SigningService {
+            requests,
+            algorithm,
+        }
+    }
+}
+
+#[cfg(any(test, feature = "test-util"))]
+impl RawSigner for SigningService {
+    fn sign_digest(&self, digest: &[u8], algorithm: Algorithm) -> Result<Vec<u8>> {
+        let (reply, response) = mpsc::channel();
+        self.requests
+            .send(SignRequest {
+                digest: digest.to_vec(),
+                algorithm,
+                reply,
+            })
+            .map_err(|_| unavailable())?;
+        response.recv().map_err(|_| unavailable())?
+    }
+
+    fn algorithm(&self) -> Algorithm {
+        self.algorithm
+    }
+}
+
+#[cfg(any(test, feature = "test-util"))]
+fn unavailable() -> crate::Error {
+    crate::Error::CryptoError("Signing service is unavailable".to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ExternalKey, SigningService};
+    use crate::{
+        common::crypto::{Ed25519Key, RsaKey, Sha256},
+        DkimResult, DkimSigner, DkimVerifier, FixedClock, HeaderWriter, MemoryResolver,
+    };
+
+    const MESSAGE: &str = "From: joe@example.com\r\nSubject: Hi\r\n\r\nHello\r\n";
+
+    fn verify(header: String, record: String) -> DkimResult {
+        let mut resolver = MemoryResolver::new();
+        resolver.txt_add("remote._domainkey.example.com", record);
+        let message = format!("{header}{MESSAGE}");
+        DkimVerifier::new(resolver)
+            .verify(message.as_bytes())
+            .remove(0)
+            .result
+    }
+
+    #[test]
+    fn sign_ed25519() {
+        let key = Ed25519Key::from_seed(&[1; 32]).unwrap();
+        let record = key.public_key_record().to_string();
+        let signer = DkimSigner::from_key(ExternalKey::new(SigningService::spawn(key)))
+            .domain("example.com")
+            .selector("remote")
+            .headers(["From", "Subject"]);
+        let signature = signer
+            .sign_with_clock(MESSAGE.as_bytes(), &FixedClock(1000))
+            .unwrap();
+        assert_eq!(verify(signature.to_header(), record), DkimResult::Pass);
+
+        // Signing the digest remotely gives the same signature as signing locally
+        let local = DkimSigner::from_key(Ed25519Key::from_seed(&[1; 32]).unwrap())
+            .domain("example.com")
+            .selector("remote")
+            .headers(["From", "Subject"])
+            .sign_with_clock(MESSAGE.as_bytes(), &FixedClock(1000))
+            .unwrap();
+        assert_eq!(signature, local);
+    }
+
+    #[test]
+    fn sign_rsa() {
+        let key = RsaKey::<Sha256>::from_pem(include_str!("../../private_key.pem")).unwrap();
+        let record = key.public_key_record().unwrap().to_string();
+        let signature = DkimSigner::from_key(ExternalKey::new(SigningService::spawn(key)))
+            .domain("example.com")
+            .selector("remote")
+            .headers(["From", "Subject"])
+            .sign(MESSAGE.as_bytes())
+            .unwrap();
+        assert_eq!(verify(signature.to_header(), record), DkimResult::Pass);
+    }
+}
@@ -0,0 +1,166 @@
+use crate::{Error, Result};
+use std::{
+    collections::HashMap,
+    net::{IpAddr, Ipv4Addr, Ipv6Addr},
+};
+
+/// DNS lookups required to authenticate a message.
+///
+/// Only TXT lookups are needed for DKIM and ARC. The address, MX and PTR
+/// lookups used by SPF fail with [`Error::DnsError`] unless implemented.
+pub trait Resolver {
+    /// Returns the TXT records published at `name`, with the character-strings
+    /// of each record already concatenated.
+    fn txt_lookup(&self, name: &str) -> Result<Vec<Vec<u8>>>;
+
+    /// Returns the IPv4 addresses (A records) of `name`.
+    fn ipv4_lookup(&self, name: &str) -> Result<Vec<Ipv4Addr>> {
+        Err(unsupported("A", name))
+    }
+
+    /// Returns the IPv6 addresses (AAAA records) of `name`.
+    fn ipv6_lookup(&self, name: &str) -> Result<Vec<Ipv6Addr>> {
+        Err(unsupported("AAAA", name))
+    }
+
+    /// Returns the mail exchangers of `name`, ordered by preference.
+    fn mx_lookup(&self, name: &str) -> Result<Vec<String>> {
+        Err(unsupported("MX", name))
+    }
+
+    /// Returns the host names that `ip` points to (PTR records).
+    fn ptr_lookup(&self, ip: IpAddr) -> Result<Vec<String>> {
+        Err(unsupported("PTR", &ip.to_string()))
+    }
+}
+
+fn unsupported(record_type: &str, name: &str) -> Error {
+    Error::DnsError(format!("{record_type} lookup of {name} is not supported"))
+}
+
+impl<R: Resolver + ?Sized> Resolver for &R {
+    fn txt_lookup(&self, name: &str) -> Result<Vec<Vec<u8>>> {
+        (**self).txt_lookup(name)
+    }
+
+    fn ipv4_lookup(&self, name: &str) -> Result<Vec<Ipv4Addr>> {
+        (**self).ipv4_lookup(name)
+    }
+
+    fn ipv6_lookup(&self, name: &str) -> Result<Vec<Ipv6Addr>> {
+        (**self).ipv6_lookup(name)
+    }
+
+    fn mx_lookup(&self, name: &str) -> Result<Vec<String>> {
+        (**self).mx_lookup(name)
+    }
+
+    fn ptr_lookup(&self, ip: IpAddr) -> Result<Vec<String>> {
+        (**self).ptr_lookup(ip)
+    }
+}
+
+/// An in-memory resolver, useful for testing and for offline verification.
+#[derive(Debug, Clone, Default)]
+pub struct MemoryResolver {
+    txt: HashMap<String, Result<Vec<Vec<u8>>>>,
+    ipv4: HashMap<String, Vec<Ipv4Addr>>,
+    ipv6: HashMap<String, Vec<Ipv6Addr>>,
+    mx: HashMap<String, Vec<(u16, String)>>,
+    ptr: HashMap<IpAddr, Vec<String>>,
+    errors: HashMap<String, Error>,
+}
+
+impl MemoryResolver {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a TXT record to `name`.
+    pub fn txt_add(&mut self, name: impl AsRef<str>, value: impl Into<Vec<u8>>) {
+        if let Ok(records) = self
+            .txt
+            .entry(normalize_name(name.as_ref()))
+            .or_insert_with(|| Ok(Vec::new()))
+        {
+            records.push(value.into());
+        }
+    }
+
+    /// Makes every TXT lookup of `name` fail with `error`.
+    pub fn txt_error(&mut self, name: impl AsRef<str>, error: Error) {
+        self.txt.insert(normalize_name(name.as_ref()), Err(error));
+    }
+
+    /// Adds an A or AAAA record to `name`.
+    pub fn ip_add(&mut self, name: impl AsRef<str>, ip: IpAddr) {
+        let name = normalize_name(name.as_ref());
+        match ip {
+            IpAddr::V4(ip) => self.ipv4.entry(name).or_default().push(ip),
+            IpAddr::V6(ip) => self.ipv6.entry(name).or_default().push(ip),
+        }
+    }
+
+    /// Adds an MX record to `name`.
+    pub fn mx_add(&mut self, name: impl AsRef<str>, preference: u16, exchange: impl Into<String>) {
+        self.mx
+            .entry(normalize_name(name.as_ref()))
+            .or_default()
+            .push((preference, exchange.into()));
+    }
+
+    /// Adds a PTR record pointing `ip` to `name`.
+    pub fn ptr_add(&mut self, ip: IpAddr, name: impl Into<String>) {
+        self.ptr.entry(ip).or_default().push(name.into());
+    }
+
+    /// Makes every address, MX and TXT lookup of `name` fail with `error`.
+    pub fn dns_error(&mut self, name: impl AsRef<str>, error: Error) {
+        let name = normalize_name(name.as_ref());
+        self.txt.insert(name.clone(), Err(error.clone()));
+        self.errors.insert(name, error);
+    }
+
+    fn lookup<T: Clone>(&self, records: &HashMap<String, Vec<T>>, name: &str) -> Result<Vec<T>> {
+        let name = normalize_name(name);
+        match self.errors.get(&name) {
+            Some(err) => Err(err.clone()),
+            None => records.get(&name).cloned().ok_or(Error::DnsRecordNotFound),
+        }
+    }
+}
+
+impl Resolver for MemoryResolver {
+    fn txt_lookup(&self, name: &str) -> Result<Vec<Vec<u8>>> {
+        self.txt
+            .get(&normalize_name(name))
+            .cloned()
+            .unwrap_or(Err(Error::DnsRecordNotFound))
+    }
+
+    fn ipv4_lookup(&self, name: &str) -> Result<Vec<Ipv4Addr>> {
+        self.lookup(&self.ipv4, name)
+    }
+
+    fn ipv6_lookup(&self, name: &str) -> Result<Vec<Ipv6Addr>> {
+        self.lookup(&self.ipv6, name)
+    }
+
+    fn mx_lookup(&self, name: &str) -> Result<Vec<String>> {
+        self.lookup(&self.mx, name).map(|mut exchanges| {
+            exchanges.sort_by_key(|(preference, _)| *preference);
+            exchanges
+                .into_iter()
+                .map(|(_, exchange)| exchange)
+                .collect()
+        })
+    }
+
+    fn ptr_lookup(&self, ip: IpAddr) -> Result<Vec<String>> {
+        self.ptr.get(&ip).cloned().ok_or(Error::DnsRecordNotFound)
+    }
+}
+
+fn normalize_name(name: &str) -> String {
+    name.trim_end_matches('.').to_ascii_lowercase()
+}
@@ -0,0 +1,172 @@
+use super::{
+    sign::{SignableMessage, StreamingMessage},
+    DkimSigner, Done, Signature,
+};
+use crate::common::{
+    clock::{Clock, SystemClock},
+    crypto::{AsyncSigningKey, HashImpl},
+    headers::HeaderWriter,
+};
+use base64::{engine::general_purpose::STANDARD as BASE64_STANDARD, Engine as _};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+
+impl<T: AsyncSigningKey> DkimSigner<T, Done> {
+    /// Signs a message read from `input` without loading its body into memory,
+    /// awaiting the signature from the key instead of blocking the runtime.
+    pub async fn sign_reader_async(
+        &self,
+        input: impl AsyncRead + Unpin,
+    ) -> crate::Result<Signature> {
+        self.sign_reader_async_with_clock(input, &SystemClock).await
+    }
+
+    /// Signs a message read from `input`, reading the `t=` and `x=` times from `clock`.
+    pub async fn sign_reader_async_with_clock(
+        &self,
+        input: impl AsyncRead + Unpin,
+        clock: &impl Clock,
+    ) -> crate::Result<Signature> {
+        self.sign_stream_async(input, None, clock).await
+    }
+
+    /// Signs a message read from `input` and writes it to `output`, preceded
+    /// by its `DKIM-Signature` header. The message is copied unchanged, but is
+    /// held in memory since the header has to be written before it.
+    pub async fn sign_to_writer_async(
+        &self,
+        input: impl AsyncRead + Unpin,
+        output: impl AsyncWrite + Unpin,
+    ) -> crate::Result<Signature> {
+        self.sign_to_writer_async_with_clock(input, output, &SystemClock)
+            .await
+    }
+
+    /// Signs a message read from `input` and writes it to `output`, reading the
+    /// `t=` and `x=` times from `clock`.
+    pub async fn sign_to_writer_async_with_clock(
+        &self,
+        input: impl AsyncRead + Unpin,
+        mut output: impl AsyncWrite + Unpin,
+        clock: &impl Clock,
+    ) -> crate::Result<Signature> {
+        let mut message = Vec::new();
+        let signature = self
+            .sign_stream_async(input, Some(&mut message), clock)
+            .await?;
+
+        let mut header = Vec::new();
+        signature.write_header(&mut header);
+        output.write_all(&header).await?;
+        output.write_all(&message).await?;
+        output.flush().await?;
+
+        Ok(signature)
+    }
+
+    /// Signs a message read from `input`, also appending it to `copy`.
+    async fn sign_stream_async(
+        &self,
+        mut input: impl AsyncRead + Unpin,
+        mut copy: Option<&mut Vec<u8>>,
+        clock: &impl Clock,
+    ) -> crate::Result<Signature> {
+        let mut message = StreamingMessage::new(self, T::Hasher::hasher());
+        let mut buf = vec![0; 8192];
+        loop {
+            let len = input.read(&mut buf).await?;
+            if len == 0 {
+                break;
+            }
+            message.update(&buf[..len])?;
+            if let Some(copy) = copy.as_deref_mut() {
+                copy.extend_from_slice(&buf[..len]);
+            }
+        }
+
+        let (headers, body_hash, body_len) = message.finish()?;
+        let (canonical_headers, signed_headers) = self.canonicalize_headers(&headers)?;
+        let mut signature = self.unsigned_signature(
+            &canonical_headers,
+            signed_headers,
+            body_hash,
+            body_len,
+            clock.now(),
+        )?;
+
+        let b = self
+            .key
+            .sign(SignableMessage {
+                headers: canonical_headers,
+                signature: &signature,
+            })
+            .await?;
+
+        signature.b = BASE64_STANDARD.encode(&b).into_bytes();
+
+        Ok(signature)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{common::crypto::Ed25519Key, DkimSigner, Error, FixedClock, HeaderWriter};
+
+    fn signer() -> DkimSigner<Ed25519Key, crate::dkim::Done> {
+        DkimSigner::from_key(Ed25519Key::from_seed(&[1; 32]).unwrap())
+            .domain("example.com")
+            .selector("ed")
+            .headers(["From"])
+    }
+
+    #[tokio::test]
+    async fn sign_async() {
+        let message = "From: joe@example.com\r\n\r\nHi there\r\n";
+        let expected = signer()
+            .sign_with_clock(message.as_bytes(), &FixedClock(1000))
+            .unwrap();
+        let signature = signer()
+            .sign_reader_async_with_clock(message.as_bytes(), &FixedClock(1000))
+            .await
+            .unwrap();
+        assert_eq!(signature, expected);
+
+        let mut output = Vec::new();
+        let signature = signer()
+            .sign_to_writer_async_with_clock(message.as_bytes(), &mut output, &FixedClock(1000))
+            .await
+            .unwrap();
+        assert_eq!(signature, expected);
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            format!("{}{message}", expected.to_header())
+        );
+
+        let mut output = Vec::new();
+        let signature = signer()
+            .sign_to_writer_async(message.as_bytes(), &mut output)
+            .await
+            .unwrap();
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            format!("{}{message}", signature.to_header())
+        );
+    }
+
+    #[tokio::test]
+    async fn max_header_size() {
+        let message = "From: joe@example.com\r\nSubject: a long subject line\r\n\r\nHi\r\n";
+        let signer = signer().max_header_size(32);
+        assert_eq!(
+            signer.sign_reader_async(message.as_bytes()).await,
+            Err(Error::HeadersTooLarge)
+        );
+        let mut output = Vec::new();
+        assert_eq!(
+            signer
+                .sign_to_writer_async(message.as_bytes(), &mut output)
+                .await,
+            Err(Error::HeadersTooLarge)
+        );
+        assert!(output.is_empty());
+    }
+}
@@ -1,59 +1,190 @@
-use super::{Canonicalization, DkimSigner, Done, NeedDomain, NeedHeaders, NeedSelector};
-use crate::common::crypto::SigningKey;
+#[cfg(doc)]
+use super::Signature;
+use super::{
+    Canonicalization, DkimSigner, Done, NeedDomain, NeedHeaders, NeedSelector, Oversign,
+    DEFAULT_MAX_HEADER_SIZE, RECOMMENDED_HEADERS, UNSIGNED_HEADERS,
+};
+#[cfg(feature = "tokio")]
+use crate::common::crypto::AsyncSigningKey;
+use crate::common::crypto::{Algorithm, SigningKey};
+#[cfg(doc)]
+use crate::Error;
+use std::time::Duration;
 
 impl<T: SigningKey> DkimSigner<T> {
     pub fn from_key(key: T) -> DkimSigner<T, NeedDomain> {
+        let algorithm = SigningKey::algorithm(&key);
+        DkimSigner::with_algorithm(key, algorithm)
+    }
+}
+
+#[cfg(feature = "tokio")]
+impl<T: AsyncSigningKey> DkimSigner<T> {
+    /// Creates a signer for a key whose signatures are produced asynchronously,
+    /// see [`DkimSigner::sign_reader_async`].
+    pub fn from_async_key(key: T) -> DkimSigner<T, NeedDomain> {
+        let algorithm = AsyncSigningKey::algorithm(&key);
+        DkimSigner::with_algorithm(key, algorithm)
+    }
+}
+
+impl<T> DkimSigner<T> {
+    fn with_algorithm(key: T, algorithm: Algorithm) -> DkimSigner<T, NeedDomain> {
         DkimSigner {
             _state: Default::default(),
             template: super::Signature {
                 v: 1,
-                a: key.algorithm(),
+                a: algorithm,
                 ..Default::default()
             },
             key,
+            oversign: Oversign::None,
+            auto_headers: false,
+            copy_headers: false,
+            body_length: false,
+            emit_timestamp: true,
+            expiration: None,
+            max_header_size: DEFAULT_MAX_HEADER_SIZE,
         }
     }
 }
 
-impl<T: SigningKey> DkimSigner<T, NeedDomain> {
-    pub fn domain(mut self, domain: impl Into<String>) -> DkimSigner<T, NeedSelector> {
-        self.template.d = domain.into();
+impl<T, State> DkimSigner<T, State> {
+    fn into_state<NewState>(self) -> DkimSigner<T, NewState> {
         DkimSigner {
             _state: Default::default(),
             key: self.key,
             template: self.template,
+            oversign: self.oversign,
+            auto_headers: self.auto_headers,
+            copy_headers: self.copy_headers,
+            body_length: self.body_length,
+            emit_timestamp: self.emit_timestamp,
+            expiration: self.expiration,
+            max_header_size: self.max_header_size,
         }
     }
 }
 
-impl<T: SigningKey> DkimSigner<T, NeedSelector> {
+impl<T> DkimSigner<T, NeedDomain> {
+    pub fn domain(mut self, domain: impl Into<String>) -> DkimSigner<T, NeedSelector> {
+        self.template.d = domain.into();
+        self.into_state()
+    }
+}
+
+impl<T> DkimSigner<T, NeedSelector> {
     pub fn selector(mut self, selector: impl Into<String>) -> DkimSigner<T, NeedHeaders> {
         self.template.s = selector.into();
-        DkimSigner {
-            _state: Default::default(),
-            key: self.key,
-            template: self.template,
-        }
+        self.into_state()
     }
 }
 
-impl<T: SigningKey> DkimSigner<T, NeedHeaders> {
+impl<T> DkimSigner<T, NeedHeaders> {
     pub fn headers(
         mut self,
         headers: impl IntoIterator<Item = impl Into<String>>,
     ) -> DkimSigner<T, Done> {
         self.template.h = headers.into_iter().map(|h| h.into()).collect();
-        DkimSigner {
-            _state: Default::default(),
-            key: self.key,
-            template: self.template,
-        }
+        self.into_state()
+    }
+
+    /// Signs the [`RECOMMENDED_HEADERS`] that are present in the message, failing
+    /// with [`Error::MissingFrom`] when the message has no `From` header.
+    /// Further headers can be added with [`DkimSigner::oversign`], except for
+    /// the [`UNSIGNED_HEADERS`] which are always skipped.
+    pub fn recommended_headers(mut self) -> DkimSigner<T, Done> {
+        self.template.h = RECOMMENDED_HEADERS.iter().map(|h| h.to_string()).collect();
+        self.auto_headers = true;
+        self.into_state()
     }
 }
 
-impl<T: SigningKey> DkimSigner<T, Done> {
+impl<T> DkimSigner<T, Done> {
+    /// Adds an `l=` tag covering the whole canonicalized body, so the signature
+    /// survives content being appended to the body (e.g. mailing list footers).
     pub fn body_length(mut self, body_length: bool) -> Self {
-        self.template.l = u64::from(body_length);
+        self.body_length = body_length;
+        self
+    }
+
+    /// Signs at most the first `limit` octets of the canonicalized body and
+    /// adds the matching `l=` tag.
+    pub fn body_length_limit(mut self, limit: u64) -> Self {
+        self.template.l = Some(limit);
+        self
+    }
+
+    /// Sets the Agent or User Identifier (`i=`) on whose behalf the message is
+    /// signed, such as `user@example.com` or `@sub.example.com`. Its domain must
+    /// be the signing domain or one of its subdomains.
+    pub fn agent_user_identifier(mut self, auid: impl Into<String>) -> Self {
+        self.template.i = auid.into();
+        self
+    }
+
+    /// Signs each of `headers` once more than the number of instances present in
+    /// the message, so that instances added in transit (e.g. a second `From`)
+    /// invalidate the signature. Headers not yet listed in `h=` are added to it.
+    pub fn oversign(mut self, headers: impl IntoIterator<Item = impl Into<String>>) -> Self {
+        let mut oversigned = match self.oversign {
+            Oversign::Headers(headers) => headers,
+            Oversign::None | Oversign::All => Vec::new(),
+        };
+        for header in headers {
+            let header = header.into();
+            if self.auto_headers && is_unsigned_header(&header) {
+                continue;
+            } else if !self
+                .template
+                .h
+                .iter()
+                .any(|h| h.eq_ignore_ascii_case(&header))
+            {
+                self.template.h.push(header.clone());
+            }
+            oversigned.push(header);
+        }
+        self.oversign = Oversign::Headers(oversigned);
+        self
+    }
+
+    /// Oversigns every header listed in `h=`, see [`DkimSigner::oversign`].
+    pub fn oversign_all(mut self, oversign_all: bool) -> Self {
+        self.oversign = if oversign_all {
+            Oversign::All
+        } else {
+            Oversign::None
+        };
+        self
+    }
+
+    /// Omits the `t=` signature timestamp when set to `false`.
+    pub fn timestamp(mut self, timestamp: bool) -> Self {
+        self.emit_timestamp = timestamp;
+        self
+    }
+
+    /// Adds an `x=` tag expiring the signature `expiration` after signing.
+    pub fn expiration(mut self, expiration: Duration) -> Self {
+        self.expiration = Some(expiration);
+        self
+    }
+
+    /// Limits the header section buffered when signing a stream, such as with
+    /// [`DkimSigner::begin`] or [`DkimSigner::sign_reader`], to `max_header_size`
+    /// bytes. Larger header sections fail with [`Error::HeadersTooLarge`].
+    /// Defaults to [`DEFAULT_MAX_HEADER_SIZE`].
+    pub fn max_header_size(mut self, max_header_size: usize) -> Self {
+        self.max_header_size = max_header_size;
+        self
+    }
+
+    /// Adds a `z=` tag with a copy of the signed headers as they were at signing
+    /// time, helping receivers diagnose which headers were modified in transit.
+    /// See [`Signature::diff_copied_headers`].
+    pub fn copy_headers(mut self, copy_headers: bool) -> Self {
+        self.copy_headers = copy_headers;
         self
     }
 
@@ -67,3 +198,9 @@ impl<T: SigningKey> DkimSigner<T, Done> {
         self
     }
 }
+
+fn is_unsigned_header(header: &str) -> bool {
+    UNSIGNED_HEADERS
+        .iter()
+        .any(|unsigned| unsigned.eq_ignore_ascii_case(header))
+}
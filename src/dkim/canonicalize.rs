@@ -1,65 +1,150 @@
-use super::{Canonicalization, Signature};
-use crate::common::headers::{HeaderStream, Writable, Writer};
+use super::{Canonicalization, Oversign, Signature};
+use crate::{
+    common::{
+        crypto::{HashAlgorithm, HashContext, HashImpl, HashOutput, Sha256},
+        headers::{HeaderStream, Writable, Writer},
+    },
+    Error,
+};
 
 pub struct CanonicalBody<'a> {
     canonicalization: Canonicalization,
     body: &'a [u8],
+    limit: u64,
 }
 
 impl Writable for CanonicalBody<'_> {
-    fn write(self, hasher: &mut impl Writer) {
-        let mut crlf_seq = 0;
+    fn write(self, writer: &mut impl Writer) {
+        self.write_len(writer);
+    }
+}
+
+impl CanonicalBody<'_> {
+    /// Hashes the canonicalized body, returning the hash along with the
+    /// number of canonicalized octets that were hashed.
+    pub fn hash(self, algorithm: HashAlgorithm) -> (HashOutput, u64) {
+        match algorithm {
+            HashAlgorithm::Sha256 => {
+                let mut hasher = Sha256::hasher();
+                let len = self.write_len(&mut hasher);
+                (hasher.complete(), len)
+            }
+        }
+    }
+
+    /// Writes the canonicalized body, stopping once the body length limit
+    /// is reached, and returns the number of octets written.
+    pub fn write_len(self, writer: &mut impl Writer) -> u64 {
+        let mut canonicalizer = BodyCanonicalizer::new(self.canonicalization, self.limit);
+        canonicalizer.write(self.body, writer);
+        canonicalizer.finish(writer)
+    }
+}
+
+/// Incremental body canonicalizer that can be fed the body in arbitrary
+/// chunks, keeping pending line breaks and whitespace between calls.
+pub(crate) struct BodyCanonicalizer {
+    canonicalization: Canonicalization,
+    limit: u64,
+    remaining: u64,
+    crlf_seq: usize,
+    last_ch: u8,
+    is_empty: bool,
+}
+
+impl BodyCanonicalizer {
+    /// Creates a canonicalizer writing at most `limit` octets.
+    pub(crate) fn new(canonicalization: Canonicalization, limit: u64) -> Self {
+        BodyCanonicalizer {
+            canonicalization,
+            limit,
+            remaining: limit,
+            crlf_seq: 0,
+            last_ch: 0,
+            is_empty: true,
+        }
+    }
+
+    /// Canonicalizes the next chunk of the body into `writer`.
+    pub(crate) fn write(&mut self, chunk: &[u8], writer: &mut impl Writer) {
+        let mut hasher = LimitedWriter {
+            writer,
+            remaining: self.remaining,
+        };
         match self.canonicalization {
             Canonicalization::Relaxed => {
-                let mut last_ch = 0;
-                let mut is_empty = true;
-                for &ch in self.body {
+                for &ch in chunk {
                     match ch {
-                        b' ' | b'\t' => {
-                            while crlf_seq > 0 {
-                                hasher.write(b"\r\n");
-                                crlf_seq -= 1;
-                            }
-                            is_empty = false;
-                        }
-                        b'\n' => crlf_seq += 1,
-                        b'\r' => {}
+                        // Whitespace is only emitted once followed by other characters on
+                        // the same line, so trailing whitespace and blank lines are dropped.
+                        b' ' | b'\t' | b'\r' => {}
+                        b'\n' => self.crlf_seq += 1,
                         _ => {
-                            while crlf_seq > 0 {
+                            while self.crlf_seq > 0 {
                                 hasher.write(b"\r\n");
-                                crlf_seq -= 1;
+                                self.crlf_seq -= 1;
                             }
-                            if last_ch == b' ' || last_ch == b'\t' {
+                            if self.last_ch == b' ' || self.last_ch == b'\t' {
                                 hasher.write(b" ");
                             }
                             hasher.write(&[ch]);
-                            is_empty = false;
+                            self.is_empty = false;
                         }
                     }
-                    last_ch = ch;
-                }
-                if !is_empty {
-                    hasher.write(b"\r\n");
+                    self.last_ch = ch;
                 }
             }
             Canonicalization::Simple => {
-                for &ch in self.body {
+                for &ch in chunk {
                     match ch {
-                        b'\n' => crlf_seq += 1,
+                        b'\n' => self.crlf_seq += 1,
                         b'\r' => {}
                         _ => {
-                            while crlf_seq > 0 {
+                            while self.crlf_seq > 0 {
                                 hasher.write(b"\r\n");
-                                crlf_seq -= 1;
+                                self.crlf_seq -= 1;
                             }
                             hasher.write(&[ch]);
                         }
                     }
                 }
-                if crlf_seq == 0 && !self.body.is_empty() {
+            }
+        }
+        self.remaining = hasher.remaining;
+    }
+
+    /// Writes the final line break and returns the number of octets written.
+    pub(crate) fn finish(self, writer: &mut impl Writer) -> u64 {
+        let mut hasher = LimitedWriter {
+            writer,
+            remaining: self.remaining,
+        };
+        match self.canonicalization {
+            Canonicalization::Relaxed => {
+                if !self.is_empty {
                     hasher.write(b"\r\n");
                 }
             }
+            Canonicalization::Simple => {
+                // The body always ends with exactly one CRLF, even when empty.
+                hasher.write(b"\r\n");
+            }
+        }
+        self.limit - hasher.remaining
+    }
+}
+
+struct LimitedWriter<'a, W> {
+    writer: &'a mut W,
+    remaining: u64,
+}
+
+impl<W: Writer> Writer for LimitedWriter<'_, W> {
+    fn write(&mut self, buf: &[u8]) {
+        let len = std::cmp::min(buf.len() as u64, self.remaining) as usize;
+        if len > 0 {
+            self.writer.write(&buf[..len]);
+            self.remaining -= len as u64;
         }
     }
 }
@@ -115,17 +200,22 @@ impl Canonicalization {
         }
     }
 
-    pub fn canonical_body<'a>(&self, body: &'a [u8], l: u64) -> CanonicalBody<'a> {
+    /// Canonicalizes `body`, limited to its first `l` canonicalized octets
+    /// when set.
+    pub fn canonical_body<'a>(&self, body: &'a [u8], l: Option<u64>) -> CanonicalBody<'a> {
         CanonicalBody {
             canonicalization: *self,
-            body: if l == 0 || body.is_empty() {
-                body
-            } else {
-                &body[..std::cmp::min(l as usize, body.len())]
-            },
+            body,
+            limit: l.unwrap_or(u64::MAX),
         }
     }
 
+    /// Creates an incremental canonicalizer for a body limited to its first
+    /// `l` canonicalized octets when set.
+    pub(crate) fn body_canonicalizer(&self, l: Option<u64>) -> BodyCanonicalizer {
+        BodyCanonicalizer::new(*self, l.unwrap_or(u64::MAX))
+    }
+
     pub fn serialize_name(&self, writer: &mut impl Writer) {
         writer.write(match self {
             Canonicalization::Relaxed => b"relaxed",
@@ -136,38 +226,67 @@ impl Canonicalization {
 
 impl Signature {
     pub fn canonicalize<'x>(
+        &self,
+        message: impl HeaderStream<'x>,
+    ) -> crate::Result<(CanonicalHeaders<'x>, Vec<String>, CanonicalBody<'x>)> {
+        self.canonicalize_with(message, &Oversign::None, false)
+    }
+
+    /// Canonicalizes `message`, listing oversigned headers once more in the
+    /// returned `h=` names. With `present_only`, headers missing from the
+    /// message are left out of `h=` unless they are oversigned.
+    pub(crate) fn canonicalize_with<'x>(
         &self,
         mut message: impl HeaderStream<'x>,
-    ) -> (usize, CanonicalHeaders<'x>, Vec<String>, CanonicalBody<'x>) {
+        oversign: &Oversign,
+        present_only: bool,
+    ) -> crate::Result<(CanonicalHeaders<'x>, Vec<String>, CanonicalBody<'x>)> {
         let mut headers = Vec::with_capacity(self.h.len());
         let mut found_headers = vec![false; self.h.len()];
         let mut signed_headers = Vec::with_capacity(self.h.len());
 
         while let Some((name, value)) = message.next_header() {
+            if name.contains(&b'\n') {
+                // Header line without a colon
+                return Err(Error::MalformedMessage);
+            }
+            let field_name = name.trim_ascii_end();
             if let Some(pos) = self
                 .h
                 .iter()
-                .position(|header| name.eq_ignore_ascii_case(header.as_bytes()))
+                .position(|header| field_name.eq_ignore_ascii_case(header.as_bytes()))
             {
                 headers.push((name, value));
                 found_headers[pos] = true;
-                signed_headers.push(std::str::from_utf8(name).unwrap().into());
+                signed_headers.push(
+                    std::str::from_utf8(field_name)
+                        .map_err(|_| Error::NonUtf8HeaderName)?
+                        .into(),
+                );
             }
         }
 
-        let body = message.body();
-        let body_len = body.len();
         let canonical_headers = self.ch.canonical_headers(headers);
-        let canonical_body = self.ch.canonical_body(body, u64::MAX);
+        let canonical_body = self.cb.canonical_body(message.body(), self.l);
 
         signed_headers.reverse();
         for (header, found) in self.h.iter().zip(found_headers) {
-            if !found {
+            if (!found && !present_only) || oversign.contains(header) {
                 signed_headers.push(header.to_string());
             }
         }
 
-        (body_len, canonical_headers, signed_headers, canonical_body)
+        Ok((canonical_headers, signed_headers, canonical_body))
+    }
+}
+
+impl Oversign {
+    fn contains(&self, header: &str) -> bool {
+        match self {
+            Oversign::None => false,
+            Oversign::Headers(headers) => headers.iter().any(|h| h.eq_ignore_ascii_case(header)),
+            Oversign::All => true,
+        }
     }
 }
 
@@ -176,9 +295,163 @@ pub struct CanonicalHeaders<'a> {
     headers: Vec<(&'a [u8], &'a [u8])>,
 }
 
+impl CanonicalHeaders<'_> {
+    pub(crate) fn contains(&self, name: &str) -> bool {
+        self.headers.iter().any(|(header, _)| {
+            header
+                .trim_ascii_end()
+                .eq_ignore_ascii_case(name.as_bytes())
+        })
+    }
+
+    /// Returns the signed headers for the `z=` tag, in `h=` order, with the
+    /// whitespace around their values removed.
+    pub(crate) fn copied_headers(&self) -> Vec<(String, String)> {
+        self.headers
+            .iter()
+            .rev()
+            .map(|(name, value)| {
+                (
+                    String::from_utf8_lossy(name.trim_ascii_end()).into_owned(),
+                    String::from_utf8_lossy(value.trim_ascii()).into_owned(),
+                )
+            })
+            .collect()
+    }
+}
+
 impl Writable for CanonicalHeaders<'_> {
     fn write(self, writer: &mut impl Writer) {
         self.canonicalization
             .canonicalize_headers(self.headers.into_iter().rev(), writer)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        common::headers::{HeaderIterator, Writable},
+        dkim::{Canonicalization, Signature},
+    };
+
+    // RFC 6376, section 3.4.5
+    const MESSAGE: &[u8] = b"A: X\r\nB : Y\t\r\n\tZ  \r\n\r\n C \r\nD \t E\r\n\r\n\r\n";
+
+    /// Canonicalizes the `A` and `B` headers and the body of `message`.
+    fn canonicalize(
+        ch: Canonicalization,
+        cb: Canonicalization,
+        message: &[u8],
+    ) -> (Vec<u8>, Vec<u8>) {
+        let signature = Signature {
+            ch,
+            cb,
+            h: vec!["A".to_string(), "B".to_string()],
+            ..Default::default()
+        };
+        let (headers, _, body) = signature
+            .canonicalize(HeaderIterator::new(message))
+            .unwrap();
+
+        let (mut header_bytes, mut body_bytes) = (Vec::new(), Vec::new());
+        headers.write(&mut header_bytes);
+        body.write(&mut body_bytes);
+        (header_bytes, body_bytes)
+    }
+
+    #[test]
+    fn rfc6376_example() {
+        // Headers are canonicalized in the order of the h= list, which starts
+        // from the bottom of the header block
+        let simple_headers = b"B : Y\t\r\n\tZ  \r\nA: X\r\n".as_slice();
+        let relaxed_headers = b"b:Y Z\r\na:X\r\n".as_slice();
+        let simple_body = b" C \r\nD \t E\r\n".as_slice();
+        let relaxed_body = b" C\r\nD E\r\n".as_slice();
+
+        for (ch, cb, headers, body) in [
+            (
+                Canonicalization::Simple,
+                Canonicalization::Simple,
+                simple_headers,
+                simple_body,
+            ),
+            (
+                Canonicalization::Simple,
+                Canonicalization::Relaxed,
+                simple_headers,
+                relaxed_body,
+            ),
+            (
+                Canonicalization::Relaxed,
+                Canonicalization::Simple,
+                relaxed_headers,
+                simple_body,
+            ),
+            (
+                Canonicalization::Relaxed,
+                Canonicalization::Relaxed,
+                relaxed_headers,
+                relaxed_body,
+            ),
+        ] {
+            assert_eq!(
+                canonicalize(ch, cb, MESSAGE),
+                (headers.to_vec(), body.to_vec()),
+                "c={ch:?}/{cb:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn body_edge_cases() {
+        for (body, simple, relaxed) in [
+            // An empty body is a single CRLF in simple and empty in relaxed
+            (&b""[..], &b"\r\n"[..], &b""[..]),
+            (b"\r\n", b"\r\n", b""),
+            (b"\r\n\r\n", b"\r\n", b""),
+            // A missing final line break is added
+            (b"Hi", b"Hi\r\n", b"Hi\r\n"),
+            (b"Hi \t", b"Hi \t\r\n", b"Hi\r\n"),
+            // Trailing blank lines are removed
+            (b"Hi\r\n\r\n\r\n", b"Hi\r\n", b"Hi\r\n"),
+            (b"Hi\r\n \r\n\t\r\n", b"Hi\r\n \r\n\t\r\n", b"Hi\r\n"),
+            (
+                b"Hi\r\n\r\nthere\r\n\r\n",
+                b"Hi\r\n\r\nthere\r\n",
+                b"Hi\r\n\r\nthere\r\n",
+            ),
+        ] {
+            let message = [&b"A: X\r\n\r\n"[..], body].concat();
+            for (cb, expected) in [
+                (Canonicalization::Simple, simple),
+                (Canonicalization::Relaxed, relaxed),
+            ] {
+                assert_eq!(
+                    canonicalize(Canonicalization::Simple, cb, &message).1,
+                    expected,
+                    "{cb:?} {:?}",
+                    String::from_utf8_lossy(body)
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn header_edge_cases() {
+        // Names are trimmed and lowercased, whitespace in values is collapsed
+        let message = b"B:\r\na  :\tX   Y \r\n\r\n";
+        assert_eq!(
+            canonicalize(
+                Canonicalization::Relaxed,
+                Canonicalization::Relaxed,
+                message
+            )
+            .0,
+            b"a:X Y\r\nb:\r\n"
+        );
+        assert_eq!(
+            canonicalize(Canonicalization::Simple, Canonicalization::Relaxed, message).0,
+            b"a  :\tX   Y \r\nB:\r\n"
+        );
+    }
+}
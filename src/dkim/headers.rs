@@ -1,17 +1,42 @@
-use super::{Algorithm, Canonicalization, Signature};
-use crate::common::headers::{HeaderWriter, Writer};
+use super::{Algorithm, Canonicalization, DkimPublicKeyRecord, KeyType, Signature};
+use crate::common::{
+    crypto::HashAlgorithm,
+    headers::{HeaderWriter, Writer},
+};
+use base64::{engine::general_purpose::STANDARD as BASE64_STANDARD, Engine as _};
 use std::fmt::{Display, Formatter};
 
 impl Signature {
     pub fn write(&self, writer: &mut impl Writer, as_header: bool) {
-        let (header, new_line) = match self.ch {
-            Canonicalization::Relaxed if !as_header => (&b"dkim-signature:"[..], &b" "[..]),
-            _ => (&b"DKIM-Signature: "[..], &b"\r\n\t"[..]),
+        self.write_as(writer, "DKIM-Signature", b"v=1", as_header);
+    }
+
+    /// Writes the signature under the header `name`, starting with the `first_tag`
+    /// identifying the signature, such as `v=1` or the `i=` of an ARC set.
+    pub(crate) fn write_as(
+        &self,
+        writer: &mut impl Writer,
+        name: &str,
+        first_tag: &[u8],
+        as_header: bool,
+    ) {
+        let new_line = match self.ch {
+            Canonicalization::Relaxed if !as_header => {
+                writer.write(name.to_ascii_lowercase().as_bytes());
+                writer.write(b":");
+                &b" "[..]
+            }
+            _ => {
+                writer.write(name.as_bytes());
+                writer.write(b": ");
+                &b"\r\n\t"[..]
+            }
         };
-        writer.write(header);
-        writer.write(b"v=1; a=");
+        writer.write(first_tag);
+        writer.write(b"; a=");
         writer.write(match self.a {
-            Algorithm::RsaSha256 => b"rsa-sha256",
+            Algorithm::RsaSha256 => &b"rsa-sha256"[..],
+            Algorithm::Ed25519Sha256 => b"ed25519-sha256",
         });
         for (tag, value) in [(&b"; s="[..], &self.s), (&b"; d="[..], &self.d)] {
             writer.write(tag);
@@ -39,17 +64,71 @@ impl Signature {
             writer.write_len(h.as_bytes(), &mut bw);
         }
 
-        if self.t > 0 {
-            let value = self.t.to_string();
+        if !self.i.is_empty() {
+            let value = encode_dkim_quoted_printable(self.i.as_bytes());
+            let value_len = value.iter().map(Vec::len).sum::<usize>();
             writer.write_len(b";", &mut bw);
-            if bw + 2 + value.len() >= 76 {
+            if bw + 2 + value_len >= 76 {
                 writer.write(new_line);
                 bw = 1;
             } else {
                 writer.write_len(b" ", &mut bw);
             }
-            writer.write_len(b"t=", &mut bw);
-            writer.write_len(value.as_bytes(), &mut bw);
+            writer.write_len(b"i=", &mut bw);
+            for chunk in value {
+                if bw + chunk.len() >= 76 {
+                    writer.write(new_line);
+                    bw = 1;
+                }
+                writer.write_len(&chunk, &mut bw);
+            }
+        }
+
+        for (tag, value) in [
+            (&b"l="[..], self.l),
+            (&b"t="[..], (self.t > 0).then_some(self.t)),
+            (&b"x="[..], (self.x > 0).then_some(self.x)),
+        ] {
+            if let Some(value) = value {
+                let value = value.to_string();
+                writer.write_len(b";", &mut bw);
+                if bw + tag.len() + value.len() >= 76 {
+                    writer.write(new_line);
+                    bw = 1;
+                } else {
+                    writer.write_len(b" ", &mut bw);
+                }
+                writer.write_len(tag, &mut bw);
+                writer.write_len(value.as_bytes(), &mut bw);
+            }
+        }
+
+        for (num, (name, value)) in self.z.iter().enumerate() {
+            writer.write_len(if num > 0 { b"|" } else { b";" }, &mut bw);
+            if bw + name.len() + 3 >= 76 {
+                writer.write(new_line);
+                bw = 1;
+            } else if num == 0 {
+                writer.write_len(b" ", &mut bw);
+            }
+            if num == 0 {
+                writer.write_len(b"z=", &mut bw);
+            }
+            writer.write_len(name.as_bytes(), &mut bw);
+            writer.write_len(b":", &mut bw);
+            for chunk in encode_dkim_quoted_printable(value.as_bytes()) {
+                // The separator between copied headers has to be escaped as well
+                let chunk = if chunk == b"|" {
+                    b"=7C".to_vec()
+                } else {
+                    chunk
+                };
+                if bw + chunk.len() >= 76 {
+                    writer.write(new_line);
+                    bw = 1;
+                }
+                writer.write_len(&chunk, &mut bw);
+            }
         }
 
         for (tag, value) in [(&b"; bh="[..], &self.bh), (&b"; b="[..], &self.b)] {
@@ -70,6 +149,82 @@ impl Signature {
     }
 }
 
+impl DkimPublicKeyRecord {
+    /// Creates a record for `public_key` that only accepts SHA-256 signatures.
+    pub fn new(key_type: KeyType, public_key: impl Into<Vec<u8>>) -> Self {
+        DkimPublicKeyRecord {
+            key_type,
+            public_key: public_key.into(),
+            hash_algorithms: vec![HashAlgorithm::Sha256],
+            testing: false,
+            strict: false,
+            email_only: false,
+            notes: String::new(),
+        }
+    }
+
+    pub fn write(&self, writer: &mut impl Writer) {
+        writer.write(b"v=DKIM1; k=");
+        writer.write(match self.key_type {
+            KeyType::Rsa => &b"rsa"[..],
+            KeyType::Ed25519 => b"ed25519",
+        });
+        for (num, hash_algorithm) in self.hash_algorithms.iter().enumerate() {
+            writer.write(if num > 0 { b":" } else { b"; h=" });
+            writer.write(match hash_algorithm {
+                HashAlgorithm::Sha256 => b"sha256",
+            });
+        }
+        match (self.testing, self.strict) {
+            (true, true) => writer.write(b"; t=y:s"),
+            (true, false) => writer.write(b"; t=y"),
+            (false, true) => writer.write(b"; t=s"),
+            (false, false) => {}
+        }
+        if self.email_only {
+            writer.write(b"; s=email");
+        }
+        if !self.notes.is_empty() {
+            writer.write(b"; n=");
+            for chunk in encode_dkim_quoted_printable(self.notes.as_bytes()) {
+                writer.write(&chunk);
+            }
+        }
+        writer.write(b"; p=");
+        writer.write(BASE64_STANDARD.encode(&self.public_key).as_bytes());
+    }
+
+    /// Renders the record as the character-strings of a TXT record, each at
+    /// most 255 octets long. Resolvers concatenate them back into one record.
+    pub fn to_txt_strings(&self) -> Vec<String> {
+        let mut buf = Vec::new();
+        self.write(&mut buf);
+        buf.chunks(255)
+            .map(|chunk| String::from_utf8_lossy(chunk).into_owned())
+            .collect()
+    }
+}
+
+impl Display for DkimPublicKeyRecord {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let mut buf = Vec::new();
+        self.write(&mut buf);
+        f.write_str(&String::from_utf8_lossy(&buf))
+    }
+}
+
+/// Encodes `value` as DKIM-quoted-printable (RFC 6376, section 2.11), returning
+/// one chunk per encoded octet so that folding never splits an escape sequence.
+pub(crate) fn encode_dkim_quoted_printable(value: &[u8]) -> Vec<Vec<u8>> {
+    value
+        .iter()
+        .map(|&ch| match ch {
+            0x21..=0x3A | 0x3C | 0x3E..=0x7E => vec![ch],
+            _ => format!("={ch:02X}").into_bytes(),
+        })
+        .collect()
+}
+
 impl HeaderWriter for Signature {
     fn write_header(&self, writer: &mut impl Writer) {
         self.write(writer, true);
@@ -1,10 +1,20 @@
+#[cfg(feature = "tokio")]
+pub mod async_sign;
 pub mod builder;
 pub mod canonicalize;
 pub mod headers;
+pub mod multi;
+pub mod parse;
 pub mod sign;
+pub mod table;
+pub mod verify;
 
-use crate::common::crypto::{Algorithm, SigningKey};
-use std::marker::PhantomData;
+use crate::{
+    common::crypto::{Algorithm, HashAlgorithm, HashImpl, SigningKey},
+    Error,
+};
+use sign::StreamingMessage;
+use std::{marker::PhantomData, time::Duration};
 
 // --- Enums and Structs ---
 
@@ -21,25 +31,202 @@ pub struct Signature {
     pub a: Algorithm,
     pub d: String,
     pub s: String,
+    pub i: String,
     pub b: Vec<u8>,
     pub bh: Vec<u8>,
     pub h: Vec<String>,
-    pub l: u64,
+    pub z: Vec<(String, String)>,
+    /// The number of canonicalized body octets covered by the signature,
+    /// when limited by an `l=` tag.
+    pub l: Option<u64>,
     pub t: u64,
+    pub x: u64,
     pub ch: Canonicalization,
     pub cb: Canonicalization,
 }
 
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DkimResult {
+    Pass,
+    Neutral(Error),
+    Fail(Error),
+    PermError(Error),
+    TempError(Error),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DkimOutput {
+    pub result: DkimResult,
+    pub signature: Option<Signature>,
+}
+
+/// A difference between the headers copied into the `z=` tag when signing and
+/// the headers received, see [`Signature::diff_copied_headers`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum HeaderDiff {
+    /// The header value was modified in transit.
+    Modified {
+        name: String,
+        signed: String,
+        received: String,
+    },
+    /// The header was removed in transit.
+    Removed { name: String, signed: String },
+    /// Another instance of a signed header was added in transit.
+    Added { name: String, received: String },
+}
+
+/// A DKIM public key record, published as a TXT record at
+/// `<selector>._domainkey.<domain>` (RFC 6376, section 3.6.1).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DkimPublicKeyRecord {
+    pub key_type: KeyType,
+    /// The DER encoded SubjectPublicKeyInfo of an RSA key, or the raw 32-byte
+    /// Ed25519 public key. An empty key marks the record as revoked.
+    pub public_key: Vec<u8>,
+    /// Acceptable hash algorithms (`h=`); empty allows any algorithm.
+    pub hash_algorithms: Vec<HashAlgorithm>,
+    /// The domain is testing DKIM (`t=y`).
+    pub testing: bool,
+    /// The `i=` domain must match `d=` exactly rather than be a subdomain (`t=s`).
+    pub strict: bool,
+    /// The key may only be used for email (`s=email`).
+    pub email_only: bool,
+    /// Notes for administrators (`n=`).
+    pub notes: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum KeyType {
+    #[default]
+    Rsa,
+    Ed25519,
+}
+
 // --- Builder Pattern ---
 
 #[derive(Debug, PartialEq, Eq, Clone, Default)]
-pub struct DkimSigner<T: SigningKey, State = NeedDomain> {
+pub struct DkimSigner<T, State = NeedDomain> {
     pub(crate) _state: PhantomData<State>,
     pub(crate) key: T,
     pub(crate) template: Signature,
+    pub(crate) oversign: Oversign,
+    pub(crate) auto_headers: bool,
+    pub(crate) copy_headers: bool,
+    /// Adds an `l=` tag even when the body length is not limited.
+    pub(crate) body_length: bool,
+    /// Adds a `t=` tag with the signing time.
+    pub(crate) emit_timestamp: bool,
+    /// Adds an `x=` tag expiring the signature this long after signing.
+    pub(crate) expiration: Option<Duration>,
+    /// The largest header section buffered when signing a stream.
+    pub(crate) max_header_size: usize,
+}
+
+/// Signs a message with several [`DkimSigner`]s at once, such as an RSA and an
+/// Ed25519 key or the old and new keys during a key rollover.
+#[derive(Default)]
+pub struct MultiSigner<'a> {
+    pub(crate) signers: Vec<Box<dyn multi::ParsedMessageSigner + 'a>>,
+}
+
+/// Routes messages to signers based on the address in their `From` header,
+/// like the signing and key tables of OpenDKIM.
+#[derive(Default)]
+pub struct SigningTable<'a> {
+    pub(crate) entries: Vec<(SenderPattern, Box<dyn multi::ParsedMessageSigner + 'a>)>,
+    /// Whether every matching entry signs, rather than the most specific ones.
+    pub(crate) all_matches: bool,
+}
+
+/// Sender addresses matched by a [`SigningTable`] entry.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum SenderPattern {
+    /// `user@example.com`
+    Address(String),
+    /// `example.com` or `*@example.com`
+    Domain(String),
+    /// `*.example.com`, matching subdomains of `example.com`
+    Subdomains(String),
+    /// `*`
+    Any,
+}
+
+/// Incremental signing context returned by [`DkimSigner::begin`].
+pub struct DkimSigningContext<'a, T: SigningKey> {
+    pub(crate) signer: &'a DkimSigner<T, Done>,
+    pub(crate) message: StreamingMessage<<T::Hasher as HashImpl>::Context>,
+}
+
+/// Headers signed by [`DkimSigner::recommended_headers`] when present in the
+/// message: the set recommended by RFC 6376, section 5.4.1, along with the
+/// headers identifying the message and describing its MIME structure.
+pub const RECOMMENDED_HEADERS: &[&str] = &[
+    "From",
+    "Sender",
+    "Reply-To",
+    "Subject",
+    "Date",
+    "Message-ID",
+    "To",
+    "Cc",
+    "MIME-Version",
+    "Content-Type",
+    "Content-Transfer-Encoding",
+    "Content-ID",
+    "Content-Description",
+    "Resent-Date",
+    "Resent-From",
+    "Resent-Sender",
+    "Resent-To",
+    "Resent-Cc",
+    "Resent-Message-ID",
+    "In-Reply-To",
+    "References",
+    "List-Id",
+    "List-Help",
+    "List-Unsubscribe",
+    "List-Unsubscribe-Post",
+    "List-Subscribe",
+    "List-Post",
+    "List-Owner",
+    "List-Archive",
+];
+
+/// The default largest header section, in bytes, buffered when signing a
+/// stream, see [`DkimSigner::max_header_size`].
+pub const DEFAULT_MAX_HEADER_SIZE: usize = 100 * 1024;
+
+/// Headers that are added or rewritten in transit and must not be signed.
+pub const UNSIGNED_HEADERS: &[&str] = &[
+    "Return-Path",
+    "Received",
+    "Comments",
+    "Keywords",
+    "DKIM-Signature",
+    "Authentication-Results",
+    "ARC-Seal",
+    "ARC-Message-Signature",
+    "ARC-Authentication-Results",
+];
+
+/// Headers that are signed once more than the number of instances present.
+#[derive(Debug, PartialEq, Eq, Clone, Default)]
+pub(crate) enum Oversign {
+    #[default]
+    None,
+    Headers(Vec<String>),
+    All,
 }
 
 pub struct NeedDomain;
 pub struct NeedSelector;
 pub struct NeedHeaders;
 pub struct Done;
+
+// --- Verifier ---
+
+#[derive(Debug, Clone, Default)]
+pub struct DkimVerifier<R> {
+    pub(crate) resolver: R,
+}
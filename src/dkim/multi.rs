@@ -0,0 +1,216 @@
+use super::{Canonicalization, DkimSigner, Done, MultiSigner, Signature};
+use crate::common::{
+    clock::{Clock, SystemClock},
+    crypto::{HashAlgorithm, HashOutput, SigningKey},
+    headers::{HeaderIterator, HeaderStream},
+};
+use std::slice::Iter;
+
+impl<'a> MultiSigner<'a> {
+    pub fn new() -> Self {
+        MultiSigner {
+            signers: Vec::new(),
+        }
+    }
+
+    pub fn signer<T: SigningKey + 'a>(mut self, signer: DkimSigner<T, Done>) -> Self {
+        self.signers.push(Box::new(signer));
+        self
+    }
+
+    /// Signs `message` with every signer, returning the signatures in the
+    /// order the signers were added. Writing their headers in that order
+    /// before the message leaves the first signature at the top; when
+    /// prepending them one at a time, prepend in reverse order instead.
+    pub fn sign(&self, message: &[u8]) -> crate::Result<Vec<Signature>> {
+        self.sign_with_clock(message, &SystemClock)
+    }
+
+    /// Signs `message`, reading the `t=` and `x=` times from `clock`.
+    pub fn sign_with_clock(
+        &self,
+        message: &[u8],
+        clock: &impl Clock,
+    ) -> crate::Result<Vec<Signature>> {
+        let mut iterator = HeaderIterator::new(message);
+        let headers = (&mut iterator).collect::<Vec<_>>();
+        let body = iterator.body();
+
+        sign_parsed_message(
+            self.signers.iter().map(Box::as_ref),
+            &headers,
+            body,
+            clock.now(),
+        )
+    }
+}
+
+/// Signs an already parsed message with each of `signers`, computing every
+/// distinct body hash only once.
+pub(crate) fn sign_parsed_message<'s, S: ParsedMessageSigner + ?Sized + 's>(
+    signers: impl IntoIterator<Item = &'s S>,
+    headers: &[(&[u8], &[u8])],
+    body: &[u8],
+    now: u64,
+) -> crate::Result<Vec<Signature>> {
+    // Signers sharing a body canonicalization and length limit share the body hash
+    let mut body_hashes: Vec<(BodyHashKey, HashOutput, u64)> = Vec::new();
+    let mut signatures = Vec::new();
+    for signer in signers {
+        let template = signer.template();
+        let key = BodyHashKey {
+            algorithm: HashAlgorithm::from(template.a),
+            canonicalization: template.cb,
+            limit: template.l,
+        };
+        let (body_hash, body_len) = match body_hashes.iter().find(|(k, _, _)| *k == key) {
+            Some((_, body_hash, body_len)) => (body_hash.clone(), *body_len),
+            None => {
+                let (body_hash, body_len) = key
+                    .canonicalization
+                    .canonical_body(body, key.limit)
+                    .hash(key.algorithm);
+                body_hashes.push((key, body_hash.clone(), body_len));
+                (body_hash, body_len)
+            }
+        };
+
+        signatures.push(signer.sign_parsed(
+            ParsedMessage {
+                headers: headers.iter(),
+                body,
+            },
+            body_hash,
+            body_len,
+            now,
+        )?);
+    }
+
+    Ok(signatures)
+}
+
+#[derive(PartialEq, Eq)]
+struct BodyHashKey {
+    algorithm: HashAlgorithm,
+    canonicalization: Canonicalization,
+    limit: Option<u64>,
+}
+
+/// A message whose headers have already been split by [`HeaderIterator`].
+pub(crate) struct ParsedMessage<'a, 'x> {
+    headers: Iter<'a, (&'x [u8], &'x [u8])>,
+    body: &'x [u8],
+}
+
+impl<'x> HeaderStream<'x> for ParsedMessage<'_, 'x> {
+    fn next_header(&mut self) -> Option<(&'x [u8], &'x [u8])> {
+        self.headers.next().copied()
+    }
+
+    fn body(&mut self) -> &'x [u8] {
+        self.body
+    }
+}
+
+/// Object safe view of a [`DkimSigner`], so signers with different key types
+/// can be combined.
+pub(crate) trait ParsedMessageSigner {
+    fn template(&self) -> &Signature;
+    fn sign_parsed(
+        &self,
+        message: ParsedMessage<'_, '_>,
+        body_hash: HashOutput,
+        body_len: u64,
+        now: u64,
+    ) -> crate::Result<Signature>;
+}
+
+impl<T: SigningKey> ParsedMessageSigner for DkimSigner<T, Done> {
+    fn template(&self) -> &Signature {
+        &self.template
+    }
+
+    fn sign_parsed(
+        &self,
+        message: ParsedMessage<'_, '_>,
+        body_hash: HashOutput,
+        body_len: u64,
+        now: u64,
+    ) -> crate::Result<Signature> {
+        let (canonical_headers, signed_headers, _) =
+            self.template
+                .canonicalize_with(message, &self.oversign, self.auto_headers)?;
+        self.sign_canonical(canonical_headers, signed_headers, body_hash, body_len, now)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        common::crypto::{Ed25519Key, RsaKey, Sha256},
+        dkim::Canonicalization,
+        DkimResult, DkimSigner, DkimVerifier, HeaderWriter, MemoryResolver, MultiSigner,
+    };
+
+    const MESSAGE: &str = "From: joe@example.com\r\nSubject: Hi\r\n\r\nHello  there\r\n\r\n";
+
+    #[test]
+    fn sign_rsa_and_ed25519() {
+        let rsa = RsaKey::<Sha256>::from_pem(include_str!("../../private_key.pem")).unwrap();
+        let ed = Ed25519Key::from_seed(&[1; 32]).unwrap();
+        let mut resolver = MemoryResolver::new();
+        resolver.txt_add(
+            "rsa._domainkey.example.com",
+            rsa.public_key_record().unwrap().to_string(),
+        );
+        resolver.txt_add(
+            "ed._domainkey.example.com",
+            ed.public_key_record().to_string(),
+        );
+
+        let signatures = MultiSigner::new()
+            .signer(
+                DkimSigner::from_key(rsa)
+                    .domain("example.com")
+                    .selector("rsa")
+                    .headers(["From", "Subject"]),
+            )
+            .signer(
+                DkimSigner::from_key(ed)
+                    .domain("example.com")
+                    .selector("ed")
+                    .headers(["From"]),
+            )
+            .signer(
+                DkimSigner::from_key(Ed25519Key::from_seed(&[1; 32]).unwrap())
+                    .domain("example.com")
+                    .selector("ed")
+                    .headers(["From"])
+                    .body_canonicalization(Canonicalization::Simple),
+            )
+            .sign(MESSAGE.as_bytes())
+            .unwrap();
+        assert_eq!(
+            signatures.iter().map(|s| s.s.as_str()).collect::<Vec<_>>(),
+            ["rsa", "ed", "ed"]
+        );
+
+        // Both SHA-256 signers with relaxed body canonicalization share one
+        // body hash, while simple canonicalization gets its own
+        assert_eq!(signatures[0].bh, signatures[1].bh);
+        assert_ne!(signatures[1].bh, signatures[2].bh);
+
+        // Writing the headers in order keeps the first signature on top
+        let mut message = String::new();
+        for signature in &signatures {
+            message.push_str(&signature.to_header());
+        }
+        message.push_str(MESSAGE);
+        let output = DkimVerifier::new(resolver).verify(message.as_bytes());
+        assert_eq!(output.len(), 3);
+        for (output, signature) in output.iter().zip(&signatures) {
+            assert_eq!(output.result, DkimResult::Pass);
+            assert_eq!(output.signature.as_ref(), Some(signature));
+        }
+    }
+}
@@ -0,0 +1,515 @@
+use super::{Canonicalization, DkimPublicKeyRecord, KeyType, Signature};
+use crate::{
+    common::{
+        crypto::{Algorithm, Ed25519PublicKey, HashAlgorithm, RsaPublicKey, VerifyingKey},
+        parse::{decode_dkim_quoted_printable, decode_qp_section, split_list, strip_fws, TagList},
+    },
+    Error,
+};
+use base64::{engine::general_purpose::STANDARD as BASE64_STANDARD, Engine as _};
+use std::str::FromStr;
+
+impl Signature {
+    /// Parses a `DKIM-Signature` header. `header` may either be the header value
+    /// alone or the complete header including its field name.
+    pub fn parse(header: &[u8]) -> crate::Result<Self> {
+        Self::parse_tags(strip_field_name(header, b"DKIM-Signature"), None)
+    }
+
+    /// Parses the value of an `ARC-Message-Signature` header, returning the
+    /// signature along with its instance number. Unlike a `DKIM-Signature`,
+    /// it has no `v=` tag and its `i=` tag holds the instance number.
+    pub(crate) fn parse_arc(header: &[u8]) -> crate::Result<(Self, u32)> {
+        let mut instance = 0;
+        Self::parse_tags(header, Some(&mut instance)).map(|signature| (signature, instance))
+    }
+
+    fn parse_tags(header: &[u8], mut instance: Option<&mut u32>) -> crate::Result<Self> {
+        let mut signature = Signature {
+            ch: Canonicalization::Simple,
+            cb: Canonicalization::Simple,
+            ..Default::default()
+        };
+        let mut seen_tags: Vec<&[u8]> = Vec::new();
+
+        for tag in TagList::new(header) {
+            let (name, value) = tag?;
+            if seen_tags.contains(&name) {
+                return Err(Error::DuplicateTag(tag_name(name)));
+            }
+            seen_tags.push(name);
+
+            match name {
+                b"i" if instance.is_some() => {
+                    if let Some(instance) = instance.as_deref_mut() {
+                        *instance = parse_number(name, value)?
+                            .try_into()
+                            .map_err(|_| Error::InvalidTag(tag_name(name)))?;
+                    }
+                }
+                b"v" if instance.is_some() => (),
+                b"v" => {
+                    if value != b"1" {
+                        return Err(Error::UnsupportedVersion);
+                    }
+                    signature.v = 1;
+                }
+                b"a" => signature.a = Algorithm::parse(value)?,
+                b"b" => signature.b = parse_base64(name, value)?,
+                b"bh" => signature.bh = parse_base64(name, value)?,
+                b"c" => {
+                    let (ch, cb) = Canonicalization::parse_pair(value)?;
+                    signature.ch = ch;
+                    signature.cb = cb;
+                }
+                b"d" => signature.d = parse_token(name, value)?,
+                b"s" => signature.s = parse_token(name, value)?,
+                b"i" => {
+                    signature.i = decode_dkim_quoted_printable(value)
+                        .and_then(|value| String::from_utf8(value).ok())
+                        .filter(|value| value.contains('@'))
+                        .ok_or_else(|| Error::InvalidTag(tag_name(name)))?;
+                }
+                b"h" => {
+                    signature.h = split_list(value, b':')
+                        .map(|header| parse_token(name, header))
+                        .collect::<crate::Result<_>>()?;
+                }
+                b"z" => {
+                    signature.z = split_list(value, b'|')
+                        .map(|field| parse_copied_header(name, field))
+                        .collect::<crate::Result<_>>()?;
+                }
+                b"q" if !split_list(value, b':').any(|q| q.eq_ignore_ascii_case(b"dns/txt")) => {
+                    return Err(Error::InvalidTag(tag_name(name)))
+                }
+                b"l" => signature.l = Some(parse_number(name, value)?),
+                b"t" => signature.t = parse_number(name, value)?,
+                b"x" => signature.x = parse_number(name, value)?,
+                _ => (),
+            }
+        }
+
+        let required_tags = if instance.is_some() {
+            ["i", "a", "b", "bh", "d", "h", "s"]
+        } else {
+            ["v", "a", "b", "bh", "d", "h", "s"]
+        };
+        for required in required_tags {
+            if !seen_tags.contains(&required.as_bytes()) {
+                return Err(Error::MissingTag(required.to_string()));
+            }
+        }
+        if !signature.h.iter().any(|h| h.eq_ignore_ascii_case("From")) {
+            return Err(Error::InvalidTag("h".to_string()));
+        }
+        // The expiration has to be later than the signing time (RFC 6376, section 3.5)
+        if signature.x > 0 && signature.x <= signature.t {
+            return Err(Error::InvalidTag("x".to_string()));
+        }
+
+        Ok(signature)
+    }
+}
+
+impl FromStr for Signature {
+    type Err = Error;
+
+    fn from_str(s: &str) -> crate::Result<Self> {
+        Signature::parse(s.as_bytes())
+    }
+}
+
+impl Algorithm {
+    pub(crate) fn parse(value: &[u8]) -> crate::Result<Self> {
+        if value.eq_ignore_ascii_case(b"rsa-sha256") {
+            Ok(Algorithm::RsaSha256)
+        } else if value.eq_ignore_ascii_case(b"ed25519-sha256") {
+            Ok(Algorithm::Ed25519Sha256)
+        } else {
+            Err(Error::UnsupportedAlgorithm)
+        }
+    }
+}
+
+impl Canonicalization {
+    pub(crate) fn parse(value: &[u8]) -> crate::Result<Self> {
+        if value.eq_ignore_ascii_case(b"relaxed") {
+            Ok(Canonicalization::Relaxed)
+        } else if value.eq_ignore_ascii_case(b"simple") {
+            Ok(Canonicalization::Simple)
+        } else {
+            Err(Error::UnsupportedCanonicalization)
+        }
+    }
+
+    fn parse_pair(value: &[u8]) -> crate::Result<(Self, Self)> {
+        match value.iter().position(|&ch| ch == b'/') {
+            Some(pos) => Ok((
+                Canonicalization::parse(value[..pos].trim_ascii())?,
+                Canonicalization::parse(value[pos + 1..].trim_ascii())?,
+            )),
+            None => Ok((Canonicalization::parse(value)?, Canonicalization::Simple)),
+        }
+    }
+}
+
+impl DkimPublicKeyRecord {
+    /// Parses a DKIM key record (RFC 6376, section 3.6.1). The optional `v=`
+    /// tag must come first, unknown tags are ignored and a revoked key (empty
+    /// `p=`) is returned with an empty `public_key`.
+    pub fn parse(record: &[u8]) -> crate::Result<Self> {
+        let mut result = DkimPublicKeyRecord {
+            key_type: KeyType::Rsa,
+            public_key: Vec::new(),
+            hash_algorithms: Vec::new(),
+            testing: false,
+            strict: false,
+            email_only: false,
+            notes: String::new(),
+        };
+        let mut public_key = None;
+        let mut seen_tags: Vec<&[u8]> = Vec::new();
+
+        for tag in TagList::new(record) {
+            let (name, value) = tag?;
+            if seen_tags.contains(&name) {
+                return Err(Error::DuplicateTag(tag_name(name)));
+            }
+            seen_tags.push(name);
+
+            match name {
+                b"v" if seen_tags.len() > 1 => return Err(Error::InvalidTag(tag_name(name))),
+                b"v" if value != b"DKIM1" => return Err(Error::UnsupportedVersion),
+                b"k" => result.key_type = KeyType::parse(value)?,
+                b"h" => {
+                    result.hash_algorithms = split_list(value, b':')
+                        .filter(|h| h.eq_ignore_ascii_case(b"sha256"))
+                        .map(|_| HashAlgorithm::Sha256)
+                        .collect();
+                    if result.hash_algorithms.is_empty() {
+                        return Err(Error::IncompatibleAlgorithms);
+                    }
+                }
+                b"p" => public_key = Some(strip_fws(value)),
+                b"s" => {
+                    let mut services = split_list(value, b':');
+                    if !services.any(|s| s == b"*" || s.eq_ignore_ascii_case(b"email")) {
+                        return Err(Error::InvalidTag(tag_name(name)));
+                    }
+                    result.email_only = !split_list(value, b':').any(|s| s == b"*");
+                }
+                b"t" => {
+                    for flag in split_list(value, b':') {
+                        match flag {
+                            b"y" => result.testing = true,
+                            b"s" => result.strict = true,
+                            _ => (),
+                        }
+                    }
+                }
+                b"n" => {
+                    result.notes = decode_qp_section(value)
+                        .map(|notes| String::from_utf8_lossy(&notes).into_owned())
+                        .ok_or_else(|| Error::InvalidTag(tag_name(name)))?;
+                }
+                _ => (),
+            }
+        }
+
+        match public_key {
+            Some(key) if key.is_empty() => (),
+            Some(key) => {
+                result.public_key = BASE64_STANDARD.decode(key).map_err(|_| Error::Base64)?;
+            }
+            None => return Err(Error::MissingTag("p".to_string())),
+        }
+
+        Ok(result)
+    }
+
+    /// Parses a record published as several TXT character-strings, which are
+    /// concatenated without separators.
+    pub fn from_txt_strings(
+        strings: impl IntoIterator<Item = impl AsRef<[u8]>>,
+    ) -> crate::Result<Self> {
+        let record = strings
+            .into_iter()
+            .flat_map(|string| string.as_ref().to_vec())
+            .collect::<Vec<_>>();
+        DkimPublicKeyRecord::parse(&record)
+    }
+
+    pub fn is_revoked(&self) -> bool {
+        self.public_key.is_empty()
+    }
+
+    /// Decodes the public key, failing with [`Error::RevokedPublicKey`] when
+    /// the record has been revoked.
+    pub fn verifying_key(&self) -> crate::Result<Box<dyn VerifyingKey>> {
+        if self.is_revoked() {
+            return Err(Error::RevokedPublicKey);
+        }
+        Ok(match self.key_type {
+            KeyType::Rsa => Box::new(RsaPublicKey::from_der(&self.public_key)?),
+            KeyType::Ed25519 => Box::new(Ed25519PublicKey::from_bytes(&self.public_key)?),
+        })
+    }
+}
+
+impl FromStr for DkimPublicKeyRecord {
+    type Err = Error;
+
+    fn from_str(s: &str) -> crate::Result<Self> {
+        DkimPublicKeyRecord::parse(s.as_bytes())
+    }
+}
+
+impl KeyType {
+    pub(crate) fn parse(value: &[u8]) -> crate::Result<Self> {
+        if value.eq_ignore_ascii_case(b"rsa") {
+            Ok(KeyType::Rsa)
+        } else if value.eq_ignore_ascii_case(b"ed25519") {
+            Ok(KeyType::Ed25519)
+        } else {
+            Err(Error::UnsupportedKeyType)
+        }
+    }
+}
+
+/// Removes a leading `name:` field name, if present.
+pub(crate) fn strip_field_name<'x>(header: &'x [u8], name: &[u8]) -> &'x [u8] {
+    let trimmed = header.trim_ascii_start();
+    match trimmed.iter().position(|&ch| ch == b':') {
+        Some(pos) if trimmed[..pos].trim_ascii_end().eq_ignore_ascii_case(name) => {
+            &trimmed[pos + 1..]
+        }
+        _ => header,
+    }
+}
+
+pub(crate) fn tag_name(name: &[u8]) -> String {
+    String::from_utf8_lossy(name).into_owned()
+}
+
+/// Parses a value that may not contain whitespace, such as a domain or selector.
+pub(crate) fn parse_token(name: &[u8], value: &[u8]) -> crate::Result<String> {
+    if !value.is_empty() && !value.iter().any(|ch| ch.is_ascii_whitespace()) {
+        if let Ok(value) = std::str::from_utf8(value) {
+            return Ok(value.to_string());
+        }
+    }
+    Err(Error::InvalidTag(tag_name(name)))
+}
+
+pub(crate) fn parse_number(name: &[u8], value: &[u8]) -> crate::Result<u64> {
+    std::str::from_utf8(value)
+        .ok()
+        .filter(|value| value.bytes().all(|ch| ch.is_ascii_digit()))
+        .and_then(|value| value.parse().ok())
+        .ok_or_else(|| Error::InvalidTag(tag_name(name)))
+}
+
+pub(crate) fn parse_base64(name: &[u8], value: &[u8]) -> crate::Result<Vec<u8>> {
+    let value = strip_fws(value);
+    if !value.is_empty() && BASE64_STANDARD.decode(&value).is_ok() {
+        Ok(value)
+    } else {
+        Err(Error::InvalidTag(tag_name(name)))
+    }
+}
+
+fn parse_copied_header(name: &[u8], field: &[u8]) -> crate::Result<(String, String)> {
+    field
+        .iter()
+        .position(|&ch| ch == b':')
+        .and_then(|pos| {
+            let header = std::str::from_utf8(field[..pos].trim_ascii()).ok()?;
+            let value = decode_dkim_quoted_printable(&field[pos + 1..])?;
+            Some((header.to_string(), String::from_utf8(value).ok()?))
+        })
+        .filter(|(header, _)| !header.is_empty())
+        .ok_or_else(|| Error::InvalidTag(tag_name(name)))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        common::crypto::{Algorithm, Ed25519Key, HashAlgorithm, RsaKey, Sha256},
+        dkim::{Canonicalization, DkimPublicKeyRecord, KeyType, Signature},
+        Error,
+    };
+
+    const BASE: [&str; 7] = [
+        "v=1",
+        "a=rsa-sha256",
+        "d=example.com",
+        "s=sel",
+        "h=From:To",
+        "bh=YWJj",
+        "b=ZGVm",
+    ];
+
+    /// Parses a valid signature with some of its tags replaced or added.
+    fn parse(tags: &str) -> crate::Result<Signature> {
+        let tags = tags.split("; ").collect::<Vec<_>>();
+        let name = |tag: &str| tag.split('=').next().unwrap().to_string();
+        BASE.iter()
+            .filter(|tag| !tags.iter().any(|t| name(t) == name(tag)))
+            .chain(&tags)
+            .copied()
+            .collect::<Vec<_>>()
+            .join("; ")
+            .parse()
+    }
+
+    #[test]
+    fn parse_signature() {
+        let signature: Signature = concat!(
+            "DKIM-Signature: v=1; a=ed25519-sha256; c=relaxed/simple;\r\n",
+            "\td=Example.com; s=sel; i=joe=40sub.example.com; q=dns/txt; unknown=tag;\r\n",
+            "\th=From : To:\r\n\tSubject; l=12; t=100; x=200;\r\n",
+            "\tz=From:joe=40example.com|Subject:a=20b; bh=YW Jj;\r\n",
+            "\tb=ZG\r\n\t Vm\r\n"
+        )
+        .parse()
+        .unwrap();
+
+        assert_eq!(signature.a, Algorithm::Ed25519Sha256);
+        assert_eq!(
+            (signature.ch, signature.cb),
+            (Canonicalization::Relaxed, Canonicalization::Simple)
+        );
+        assert_eq!(signature.d, "Example.com");
+        assert_eq!(signature.i, "joe@sub.example.com");
+        assert_eq!(signature.h, ["From", "To", "Subject"]);
+        assert_eq!(
+            (signature.l, signature.t, signature.x),
+            (Some(12), 100, 200)
+        );
+        assert_eq!(
+            signature.z,
+            [
+                ("From".to_string(), "joe@example.com".to_string()),
+                ("Subject".to_string(), "a b".to_string())
+            ]
+        );
+        assert_eq!(signature.bh, b"YWJj");
+        assert_eq!(signature.b, b"ZGVm");
+    }
+
+    #[test]
+    fn parse_errors() {
+        for (tags, err) in [
+            ("v=2", Error::UnsupportedVersion),
+            ("s=sel; s=other", Error::DuplicateTag("s".into())),
+            ("a=rsa-sha1", Error::UnsupportedAlgorithm),
+            ("c=relaxed/fancy", Error::UnsupportedCanonicalization),
+            ("i=joe.example.com", Error::InvalidTag("i".into())),
+            ("l=-1", Error::InvalidTag("l".into())),
+            ("t=soon", Error::InvalidTag("t".into())),
+            ("q=dns/xml", Error::InvalidTag("q".into())),
+            ("z=From", Error::InvalidTag("z".into())),
+            ("t=100; x=100", Error::InvalidTag("x".into())),
+            ("t=100; x=99", Error::InvalidTag("x".into())),
+            ("h=To", Error::InvalidTag("h".into())),
+            ("b=!!", Error::InvalidTag("b".into())),
+            ("bh=", Error::InvalidTag("bh".into())),
+            ("d=a b", Error::InvalidTag("d".into())),
+        ] {
+            assert_eq!(parse(tags), Err(err), "{tags}");
+        }
+
+        for (header, err) in [
+            ("a=rsa-sha256; b=ZGVm", Error::MissingTag("v".into())),
+            (
+                "v=1; a=rsa-sha256; b=ZGVm; bh=YWJj; d=x; s=y",
+                Error::MissingTag("h".into()),
+            ),
+            ("v=1; 1a=x", Error::InvalidTag("1a".into())),
+        ] {
+            assert_eq!(header.parse::<Signature>(), Err(err), "{header}");
+        }
+
+        assert!(parse("t=100; x=101").is_ok());
+    }
+
+    #[test]
+    fn parse_key_record() {
+        let record: DkimPublicKeyRecord = concat!(
+            "v=DKIM1; k=ed25519; h=sha1 : SHA256; s=email:tlsrpt; t=y:s:x; unknown=tag;\r\n",
+            "\tn=Key=20for=3Bjoe=0D=0A; p=YW Jj\r\n\t ZA=="
+        )
+        .parse()
+        .unwrap();
+        assert_eq!(record.key_type, KeyType::Ed25519);
+        assert_eq!(record.hash_algorithms, [HashAlgorithm::Sha256]);
+        assert_eq!(
+            (record.email_only, record.testing, record.strict),
+            (true, true, true)
+        );
+        assert_eq!(record.notes, "Key for;joe\r\n");
+        assert_eq!(record.public_key, b"abcd");
+        assert!(!record.is_revoked());
+
+        let record: DkimPublicKeyRecord = "p=YWJjZA==; s=tlsrpt : *".parse().unwrap();
+        assert_eq!(record.key_type, KeyType::Rsa);
+        assert!(record.hash_algorithms.is_empty());
+        assert!(!record.email_only);
+
+        let record: DkimPublicKeyRecord = "v=DKIM1; k=rsa; p=".parse().unwrap();
+        assert!(record.is_revoked());
+        assert_eq!(record.verifying_key().err(), Some(Error::RevokedPublicKey));
+
+        let record =
+            DkimPublicKeyRecord::from_txt_strings(["v=DKIM1; k=ed25519;", " p=YWJj", "ZA=="])
+                .unwrap();
+        assert_eq!(record.public_key, b"abcd");
+    }
+
+    #[test]
+    fn parse_key_record_errors() {
+        for (record, err) in [
+            ("k=rsa; v=DKIM1; p=YWJj", Error::InvalidTag("v".into())),
+            ("p=YWJj; v=DKIM1", Error::InvalidTag("v".into())),
+            ("v=DKIM2; p=YWJj", Error::UnsupportedVersion),
+            ("v=DKIM1; k=dsa; p=YWJj", Error::UnsupportedKeyType),
+            ("v=DKIM1; h=sha1; p=YWJj", Error::IncompatibleAlgorithms),
+            ("v=DKIM1; s=tlsrpt; p=YWJj", Error::InvalidTag("s".into())),
+            ("v=DKIM1; n=a=2; p=YWJj", Error::InvalidTag("n".into())),
+            ("v=DKIM1; p=!!", Error::Base64),
+            ("v=DKIM1; k=rsa", Error::MissingTag("p".into())),
+            ("p=YWJj; p=YWJj", Error::DuplicateTag("p".into())),
+        ] {
+            assert_eq!(record.parse::<DkimPublicKeyRecord>(), Err(err), "{record}");
+        }
+    }
+
+    #[test]
+    fn match_key_record() {
+        let rsa = RsaKey::<Sha256>::from_pem(include_str!("../../private_key.pem")).unwrap();
+        let ed = Ed25519Key::from_seed(&[1; 32]).unwrap();
+        let rsa_record = rsa.public_key_record().unwrap();
+        let ed_record = ed.public_key_record();
+
+        for record in [&rsa_record, &ed_record] {
+            let parsed = DkimPublicKeyRecord::from_txt_strings(record.to_txt_strings()).unwrap();
+            assert_eq!(&parsed, record);
+        }
+        assert!(rsa.matches_record(&rsa_record));
+        assert!(ed.matches_record(&ed_record));
+        assert!(!rsa.matches_record(&ed_record));
+        assert!(!ed.matches_record(&rsa_record));
+
+        let other_ed = Ed25519Key::from_seed(&[2; 32]).unwrap();
+        assert!(!other_ed.matches_record(&ed_record));
+        let mut other_rsa = rsa_record.clone();
+        other_rsa.public_key[40] ^= 1;
+        assert!(!rsa.matches_record(&other_rsa));
+
+        // A key published with the other key type does not match
+        let mut wrong_type = ed_record.clone();
+        wrong_type.key_type = KeyType::Rsa;
+        assert!(!ed.matches_record(&wrong_type));
+    }
+}
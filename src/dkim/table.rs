@@ -0,0 +1,388 @@
+use super::{multi::sign_parsed_message, DkimSigner, Done, SenderPattern, Signature, SigningTable};
+use crate::{
+    common::{
+        clock::{Clock, SystemClock},
+        crypto::{Ed25519Key, RsaKey, Sha256, SigningKey},
+        headers::{HeaderIterator, HeaderStream},
+        parse::parse_address,
+    },
+    Error,
+};
+use std::path::Path;
+
+impl<'a> SigningTable<'a> {
+    pub fn new() -> Self {
+        SigningTable {
+            entries: Vec::new(),
+            all_matches: false,
+        }
+    }
+
+    /// Signs messages sent from addresses matching `pattern` with `signer`.
+    /// The pattern is either an address (`user@example.com`), a domain
+    /// (`example.com` or `*@example.com`), the subdomains of a domain
+    /// (`*.example.com`) or `*` to match every sender. Several entries may
+    /// share a pattern, for example to sign with both an RSA and an Ed25519 key.
+    pub fn entry<T: SigningKey + 'a>(mut self, pattern: &str, signer: DkimSigner<T, Done>) -> Self {
+        self.entries
+            .push((SenderPattern::parse(pattern), Box::new(signer)));
+        self
+    }
+
+    /// Signs messages with every matching entry instead of only the most
+    /// specific ones, so that a message can also be signed by a third party
+    /// such as its email service provider.
+    pub fn sign_all_matches(mut self, all_matches: bool) -> Self {
+        self.all_matches = all_matches;
+        self
+    }
+
+    /// Signs `message` with the entries matching its `From` address, in table
+    /// order. Only the entries with the most specific matching pattern sign,
+    /// an address being more specific than its domain, a domain more specific
+    /// than its parent domains and `*` the least specific, unless
+    /// [`SigningTable::sign_all_matches`] is set. Returns no signatures when no
+    /// entry matches.
+    pub fn sign(&self, message: &[u8]) -> crate::Result<Vec<Signature>> {
+        self.sign_with_clock(message, &SystemClock)
+    }
+
+    /// Signs `message`, reading the `t=` and `x=` times from `clock`.
+    pub fn sign_with_clock(
+        &self,
+        message: &[u8],
+        clock: &impl Clock,
+    ) -> crate::Result<Vec<Signature>> {
+        let mut iterator = HeaderIterator::new(message);
+        let headers = (&mut iterator).collect::<Vec<_>>();
+        let body = iterator.body();
+
+        let (local_part, domain) = headers
+            .iter()
+            .find(|(name, _)| name.trim_ascii_end().eq_ignore_ascii_case(b"From"))
+            .and_then(|(_, value)| parse_address(value))
+            .ok_or(Error::MissingFrom)?;
+
+        let matches = self
+            .entries
+            .iter()
+            .filter(|(pattern, _)| pattern.matches(&local_part, &domain))
+            .collect::<Vec<_>>();
+        let specificity = matches
+            .iter()
+            .map(|(pattern, _)| pattern.specificity())
+            .max();
+
+        sign_parsed_message(
+            matches
+                .into_iter()
+                .filter(|(pattern, _)| {
+                    self.all_matches || Some(pattern.specificity()) == specificity
+                })
+                .map(|(_, signer)| signer.as_ref()),
+            &headers,
+            body,
+            clock.now(),
+        )
+    }
+}
+
+impl SigningTable<'static> {
+    /// Loads a signing table from a file, see [`SigningTable::parse`]. Relative
+    /// key paths are resolved against the directory containing the table.
+    pub fn from_file(path: impl AsRef<Path>) -> crate::Result<Self> {
+        let path = path.as_ref();
+        let table = std::fs::read_to_string(path)?;
+        Self::parse_with_base(&table, path.parent().unwrap_or(Path::new("")))
+    }
+
+    /// Parses a signing table with one entry per line, made of a sender
+    /// pattern, the signing domain, the selector and the path to a PEM
+    /// encoded RSA or Ed25519 private key, separated by whitespace:
+    ///
+    /// ```text
+    /// # pattern        domain        selector   key
+    /// example.com      example.com   2024       /etc/dkim/example.com.pem
+    /// *.example.org    example.org   mail       /etc/dkim/example.org.pem
+    /// *                esp.example   esp        /etc/dkim/esp.pem
+    /// ```
+    ///
+    /// Blank lines and text following a `#` are ignored. The recommended
+    /// headers are signed, see [`DkimSigner::recommended_headers`].
+    pub fn parse(table: &str) -> crate::Result<Self> {
+        Self::parse_with_base(table, Path::new(""))
+    }
+
+    fn parse_with_base(table: &str, base: &Path) -> crate::Result<Self> {
+        let mut result = SigningTable::new();
+
+        for (num, line) in table.lines().enumerate() {
+            let line = line.split('#').next().unwrap_or_default();
+            let fields = line.split_whitespace().collect::<Vec<_>>();
+            let [pattern, domain, selector, key_path] = fields[..] else {
+                if fields.is_empty() {
+                    continue;
+                }
+                return Err(Error::InvalidSigningTable(num + 1));
+            };
+
+            let pem = std::fs::read_to_string(base.join(key_path))?;
+            result = match RsaKey::<Sha256>::from_pem(&pem) {
+                Ok(key) => result.entry(
+                    pattern,
+                    DkimSigner::from_key(key)
+                        .domain(domain)
+                        .selector(selector)
+                        .recommended_headers(),
+                ),
+                Err(Error::UnsupportedKeyType) => result.entry(
+                    pattern,
+                    DkimSigner::from_key(Ed25519Key::from_pkcs8_pem(&pem)?)
+                        .domain(domain)
+                        .selector(selector)
+                        .recommended_headers(),
+                ),
+                Err(err) => return Err(err),
+            };
+        }
+
+        Ok(result)
+    }
+}
+
+impl SenderPattern {
+    fn parse(pattern: &str) -> Self {
+        let pattern = pattern.trim().trim_end_matches('.');
+        if pattern == "*" {
+            SenderPattern::Any
+        } else if let Some(domain) = pattern
+            .strip_prefix("*.")
+            .or_else(|| pattern.strip_prefix("*@*."))
+        {
+            SenderPattern::Subdomains(domain.to_ascii_lowercase())
+        } else if let Some(domain) = pattern.strip_prefix("*@") {
+            SenderPattern::Domain(domain.to_ascii_lowercase())
+        } else if pattern.contains('@') {
+            SenderPattern::Address(pattern.to_string())
+        } else {
+            SenderPattern::Domain(pattern.to_ascii_lowercase())
+        }
+    }
+
+    fn matches(&self, local_part: &str, domain: &str) -> bool {
+        match self {
+            SenderPattern::Address(address) => {
+                address.rsplit_once('@').is_some_and(|(local, dom)| {
+                    local == local_part && dom.eq_ignore_ascii_case(domain)
+                })
+            }
+            SenderPattern::Domain(pattern) => pattern == domain,
+            SenderPattern::Subdomains(pattern) => domain
+                .strip_suffix(pattern.as_str())
+                .is_some_and(|subdomain| subdomain.ends_with('.')),
+            SenderPattern::Any => true,
+        }
+    }
+
+    /// Orders patterns from `*` to addresses, with subdomain patterns ordered
+    /// by the length of their domain.
+    fn specificity(&self) -> (u8, usize) {
+        match self {
+            SenderPattern::Address(_) => (3, 0),
+            SenderPattern::Domain(_) => (2, 0),
+            SenderPattern::Subdomains(domain) => (1, domain.len()),
+            SenderPattern::Any => (0, 0),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::SenderPattern;
+    use crate::{
+        common::{
+            clock::FixedClock,
+            crypto::{Algorithm, Ed25519Key},
+        },
+        DkimSigner, Error, SigningTable,
+    };
+
+    fn signer(domain: &str) -> DkimSigner<Ed25519Key, crate::dkim::Done> {
+        DkimSigner::from_key(Ed25519Key::from_seed(&[1; 32]).unwrap())
+            .domain(domain)
+            .selector("ed")
+            .recommended_headers()
+    }
+
+    fn signed_domains(table: &SigningTable, from: &str) -> Vec<String> {
+        table
+            .sign_with_clock(
+                format!("From: {from}\r\n\r\nHello\r\n").as_bytes(),
+                &FixedClock(1),
+            )
+            .unwrap()
+            .into_iter()
+            .map(|signature| signature.d)
+            .collect()
+    }
+
+    #[test]
+    fn parse_patterns() {
+        for (pattern, expected) in [
+            ("*", SenderPattern::Any),
+            (
+                "Joe@Example.com",
+                SenderPattern::Address("Joe@Example.com".into()),
+            ),
+            ("Example.COM.", SenderPattern::Domain("example.com".into())),
+            ("*@example.com", SenderPattern::Domain("example.com".into())),
+            (
+                "*.example.com",
+                SenderPattern::Subdomains("example.com".into()),
+            ),
+            (
+                "*@*.example.com",
+                SenderPattern::Subdomains("example.com".into()),
+            ),
+        ] {
+            assert_eq!(SenderPattern::parse(pattern), expected, "{pattern}");
+        }
+
+        for (pattern, local_part, domain, expected) in [
+            ("joe@example.com", "joe", "example.com", true),
+            ("joe@Example.com", "joe", "example.com", true),
+            ("joe@example.com", "Joe", "example.com", false),
+            ("joe@example.com", "joe", "mail.example.com", false),
+            ("example.com", "bob", "example.com", true),
+            ("*@example.com", "bob", "example.com", true),
+            ("example.com", "bob", "mail.example.com", false),
+            ("*.example.com", "bob", "mail.example.com", true),
+            ("*.example.com", "bob", "a.b.example.com", true),
+            ("*.example.com", "bob", "example.com", false),
+            ("*.example.com", "bob", "badexample.com", false),
+            ("*", "bob", "example.org", true),
+        ] {
+            assert_eq!(
+                SenderPattern::parse(pattern).matches(local_part, domain),
+                expected,
+                "{pattern} {local_part}@{domain}"
+            );
+        }
+    }
+
+    #[test]
+    fn sign_most_specific() {
+        let table = SigningTable::new()
+            .entry("joe@example.com", signer("joe.example"))
+            .entry("example.com", signer("example.com"))
+            .entry("example.com", signer("example.com"))
+            .entry("*.example.com", signer("sub.example"))
+            .entry("*.mail.example.com", signer("mail.example"))
+            .entry("*", signer("esp.example"));
+
+        for (from, expected) in [
+            ("joe@example.com", &["joe.example"][..]),
+            ("Joe (comment) <joe@Example.com.>", &["joe.example"]),
+            ("bob@example.com", &["example.com", "example.com"]),
+            ("bob@a.example.com", &["sub.example"]),
+            ("bob@a.mail.example.com", &["mail.example"]),
+            ("bob@example.org", &["esp.example"]),
+        ] {
+            assert_eq!(signed_domains(&table, from), expected, "{from}");
+        }
+
+        let table = table.sign_all_matches(true);
+        assert_eq!(
+            signed_domains(&table, "bob@a.mail.example.com"),
+            ["sub.example", "mail.example", "esp.example"]
+        );
+        assert_eq!(
+            signed_domains(&table, "joe@example.com"),
+            ["joe.example", "example.com", "example.com", "esp.example"]
+        );
+
+        let table = SigningTable::new().entry("example.com", signer("example.com"));
+        assert!(signed_domains(&table, "bob@example.org").is_empty());
+        assert_eq!(
+            table.sign(b"To: bob@example.com\r\n\r\n").err(),
+            Some(Error::MissingFrom)
+        );
+    }
+
+    #[test]
+    fn from_file() {
+        let dir = std::env::temp_dir().join(format!("signing-table-{}", std::process::id()));
+        std::fs::create_dir_all(dir.join("keys")).unwrap();
+        std::fs::write(
+            dir.join("keys/rsa.pem"),
+            include_str!("../../private_key.pem"),
+        )
+        .unwrap();
+        std::fs::write(
+            dir.join("keys/ed.pem"),
+            Ed25519Key::from_seed(&[1; 32])
+                .unwrap()
+                .to_pkcs8_pem()
+                .unwrap(),
+        )
+        .unwrap();
+        std::fs::write(
+            dir.join("table"),
+            concat!(
+                "# pattern domain selector key\n",
+                "\n",
+                "example.com   example.com  rsa  keys/rsa.pem\n",
+                "example.com   example.com  ed   keys/ed.pem  # Ed25519\n",
+                "*             esp.example  esp  keys/rsa.pem\n",
+            ),
+        )
+        .unwrap();
+
+        let table = SigningTable::from_file(dir.join("table"));
+        let missing = SigningTable::from_file(dir.join("missing"));
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        let table = table.unwrap();
+        let signatures = table
+            .sign_with_clock(b"From: bob@example.com\r\n\r\nHello\r\n", &FixedClock(1))
+            .unwrap();
+        assert_eq!(
+            signatures
+                .iter()
+                .map(|s| (s.d.as_str(), s.s.as_str(), s.a))
+                .collect::<Vec<_>>(),
+            [
+                ("example.com", "rsa", Algorithm::RsaSha256),
+                ("example.com", "ed", Algorithm::Ed25519Sha256)
+            ]
+        );
+        assert_eq!(signatures[0].h, ["From"]);
+        assert_eq!(signed_domains(&table, "bob@example.org"), ["esp.example"]);
+        assert!(matches!(missing, Err(Error::Io(_))));
+
+        // Relative key paths are resolved against the working directory
+        assert!(matches!(
+            SigningTable::parse("* example.com rsa private_key.pem"),
+            Ok(table) if table.entries.len() == 1
+        ));
+    }
+
+    #[test]
+    fn parse_invalid_lines() {
+        for (table, line) in [
+            ("example.com example.com rsa\n", 1),
+            ("# comment\n\n* example.com\n", 3),
+            (
+                "* example.com rsa private_key.pem\n* example.com rsa private_key.pem extra\n",
+                2,
+            ),
+            ("* example.com rsa # private_key.pem\n", 1),
+        ] {
+            assert_eq!(
+                SigningTable::parse(table).err(),
+                Some(Error::InvalidSigningTable(line)),
+                "{table:?}"
+            );
+        }
+    }
+}
@@ -0,0 +1,482 @@
+use super::{
+    Canonicalization, DkimOutput, DkimPublicKeyRecord, DkimResult, DkimVerifier, HeaderDiff,
+    Signature,
+};
+use crate::{
+    common::{
+        clock::{Clock, SystemClock},
+        crypto::{HashAlgorithm, VerifyingKey},
+        headers::{HeaderIterator, HeaderStream, Writable, Writer},
+        resolver::Resolver,
+    },
+    Error,
+};
+use base64::{engine::general_purpose::STANDARD as BASE64_STANDARD, Engine as _};
+
+impl<R: Resolver> DkimVerifier<R> {
+    pub fn new(resolver: R) -> Self {
+        DkimVerifier { resolver }
+    }
+
+    /// Verifies every `DKIM-Signature` header in `message`, returning one
+    /// result per signature in the order they appear.
+    pub fn verify(&self, message: &[u8]) -> Vec<DkimOutput> {
+        self.verify_with_clock(message, &SystemClock)
+    }
+
+    /// Verifies `message`, checking signature expiration against `clock`.
+    pub fn verify_with_clock(&self, message: &[u8], clock: &impl Clock) -> Vec<DkimOutput> {
+        let now = clock.now();
+        let mut iterator = HeaderIterator::new(message);
+        let headers = (&mut iterator).collect::<Vec<_>>();
+        let body = iterator.body();
+
+        headers
+            .iter()
+            .filter(|(name, _)| {
+                name.trim_ascii_end()
+                    .eq_ignore_ascii_case(b"DKIM-Signature")
+            })
+            .map(|&(name, value)| self.verify_signature(&headers, body, name, value, now))
+            .collect()
+    }
+
+    fn verify_signature(
+        &self,
+        headers: &[(&[u8], &[u8])],
+        body: &[u8],
+        name: &[u8],
+        value: &[u8],
+        now: u64,
+    ) -> DkimOutput {
+        match Signature::parse(value) {
+            Ok(signature) => DkimOutput::new(
+                self.verify_parsed(&signature, headers, body, (name, value), now),
+                Some(signature),
+            ),
+            Err(err) => DkimOutput::new(DkimResult::PermError(err), None),
+        }
+    }
+
+    /// Verifies a parsed `signature` found in the header `(name, value)`.
+    pub(crate) fn verify_parsed(
+        &self,
+        signature: &Signature,
+        headers: &[(&[u8], &[u8])],
+        body: &[u8],
+        (name, value): (&[u8], &[u8]),
+        now: u64,
+    ) -> DkimResult {
+        if signature.x > 0 && signature.x < now {
+            return DkimResult::Neutral(Error::SignatureExpired);
+        }
+
+        // Verify the body hash
+        let hash_algorithm = HashAlgorithm::from(signature.a);
+        let (body_hash, body_len) = signature
+            .cb
+            .canonical_body(body, signature.l)
+            .hash(hash_algorithm);
+        if signature.l.is_some_and(|l| body_len < l) {
+            return DkimResult::Fail(Error::SignatureLength);
+        } else if BASE64_STANDARD
+            .decode(&signature.bh)
+            .map_or(true, |bh| bh != body_hash.as_ref())
+        {
+            return DkimResult::Fail(Error::FailedBodyHashMatch);
+        }
+
+        // Obtain the public key
+        let (record, key) = match self.fetch_key(&signature.s, &signature.d) {
+            Ok(key) => key,
+            Err(err @ Error::DnsError(_)) => return DkimResult::TempError(err),
+            Err(err) => return DkimResult::PermError(err),
+        };
+        if !signature.is_auid_aligned(record.strict) {
+            return DkimResult::PermError(Error::FailedAuidMatch);
+        }
+
+        // Verify the header signature
+        let b = match BASE64_STANDARD.decode(&signature.b) {
+            Ok(b) => b,
+            Err(_) => return DkimResult::PermError(Error::Base64),
+        };
+        let headers_hash = hash_algorithm.hash(VerifiableMessage {
+            canonicalization: signature.ch,
+            headers: signature.select_headers(headers),
+            signature_header: (name, strip_signature(value)),
+        });
+
+        match key.verify(headers_hash.as_ref(), &b, signature.a) {
+            Ok(()) => DkimResult::Pass,
+            Err(err) if record.testing => DkimResult::Neutral(err),
+            Err(err @ Error::FailedVerification) => DkimResult::Fail(err),
+            Err(err) => DkimResult::PermError(err),
+        }
+    }
+
+    /// Fetches and parses the public key published for `selector` at `domain`.
+    pub(crate) fn fetch_key(
+        &self,
+        selector: &str,
+        domain: &str,
+    ) -> crate::Result<(DkimPublicKeyRecord, Box<dyn VerifyingKey>)> {
+        let records = self
+            .resolver
+            .txt_lookup(&format!("{selector}._domainkey.{domain}"))?;
+        let mut result = Err(Error::DnsRecordNotFound);
+        for record in records {
+            result = DkimPublicKeyRecord::parse(&record)
+                .and_then(|record| record.verifying_key().map(|key| (record, key)));
+            if result.is_ok() {
+                break;
+            }
+        }
+        result
+    }
+}
+
+impl Signature {
+    /// Picks the header instances covered by `h=`, consuming each instance
+    /// from the bottom of the header block upwards.
+    fn select_headers<'x>(&self, headers: &[(&'x [u8], &'x [u8])]) -> Vec<(&'x [u8], &'x [u8])> {
+        let mut used = vec![false; headers.len()];
+        let mut selected = Vec::with_capacity(self.h.len());
+
+        for h in &self.h {
+            if let Some((pos, header)) =
+                headers.iter().enumerate().rev().find(|(pos, (name, _))| {
+                    !used[*pos] && name.trim_ascii_end().eq_ignore_ascii_case(h.as_bytes())
+                })
+            {
+                used[pos] = true;
+                selected.push(*header);
+            }
+        }
+
+        selected
+    }
+
+    /// Compares the headers copied into the `z=` tag with those of the received
+    /// `message`, pairing instances from the bottom of the header block upwards
+    /// as verification does. Values are compared after header canonicalization.
+    /// Returns no differences when the signature has no `z=` tag.
+    pub fn diff_copied_headers(&self, message: &[u8]) -> Vec<HeaderDiff> {
+        let mut diff = Vec::new();
+        if self.z.is_empty() {
+            return diff;
+        }
+        let headers = HeaderIterator::new(message).collect::<Vec<_>>();
+        let mut seen: Vec<&str> = Vec::new();
+
+        for name in &self.h {
+            if seen.iter().any(|seen| seen.eq_ignore_ascii_case(name)) {
+                continue;
+            }
+            seen.push(name);
+
+            let mut received = headers
+                .iter()
+                .rev()
+                .filter(|(header, _)| {
+                    header
+                        .trim_ascii_end()
+                        .eq_ignore_ascii_case(name.as_bytes())
+                })
+                .map(|(_, value)| String::from_utf8_lossy(value.trim_ascii()).into_owned());
+
+            for (header, signed) in self
+                .z
+                .iter()
+                .filter(|(header, _)| header.eq_ignore_ascii_case(name))
+            {
+                match received.next() {
+                    Some(received) if self.ch.header_values_match(signed, &received) => {}
+                    Some(received) => diff.push(HeaderDiff::Modified {
+                        name: header.clone(),
+                        signed: signed.clone(),
+                        received,
+                    }),
+                    None => diff.push(HeaderDiff::Removed {
+                        name: header.clone(),
+                        signed: signed.clone(),
+                    }),
+                }
+            }
+            diff.extend(received.map(|received| HeaderDiff::Added {
+                name: name.clone(),
+                received,
+            }));
+        }
+
+        diff
+    }
+}
+
+impl Canonicalization {
+    fn header_values_match(&self, a: &str, b: &str) -> bool {
+        match self {
+            Canonicalization::Relaxed => a.split_ascii_whitespace().eq(b.split_ascii_whitespace()),
+            Canonicalization::Simple => a == b,
+        }
+    }
+}
+
+impl DkimOutput {
+    pub(crate) fn new(result: DkimResult, signature: Option<Signature>) -> Self {
+        DkimOutput { result, signature }
+    }
+}
+
+/// Removes the value of the `b=` tag from a raw `DKIM-Signature` header value,
+/// along with the header's trailing line break.
+pub(crate) fn strip_signature(value: &[u8]) -> Vec<u8> {
+    let mut result = Vec::with_capacity(value.len());
+
+    for spec in value.split_inclusive(|&ch| ch == b';') {
+        match spec.iter().position(|&ch| ch == b'=') {
+            Some(pos) if spec[..pos].trim_ascii() == b"b" => {
+                result.extend_from_slice(&spec[..pos + 1]);
+                if spec.ends_with(b";") {
+                    result.push(b';');
+                }
+            }
+            _ => result.extend_from_slice(spec),
+        }
+    }
+
+    while result.last().is_some_and(|&ch| ch == b'\n' || ch == b'\r') {
+        result.pop();
+    }
+    result
+}
+
+pub(crate) struct VerifiableMessage<'a> {
+    pub(crate) canonicalization: Canonicalization,
+    pub(crate) headers: Vec<(&'a [u8], &'a [u8])>,
+    pub(crate) signature_header: (&'a [u8], Vec<u8>),
+}
+
+impl Writable for VerifiableMessage<'_> {
+    fn write(self, writer: &mut impl Writer) {
+        let (name, value) = &self.signature_header;
+        self.canonicalization.canonicalize_headers(
+            self.headers
+                .into_iter()
+                .chain(std::iter::once((*name, value.as_slice()))),
+            writer,
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        common::crypto::{RsaKey, Sha256},
+        dkim::{HeaderDiff, Signature},
+        DkimResult, DkimSigner, DkimVerifier, Error, FixedClock, HeaderWriter, MemoryResolver,
+    };
+    use std::time::Duration;
+
+    const MESSAGE: &str = concat!(
+        "From: Joe SixPack <joe@football.example.com>\r\n",
+        "To: Suzie Q <suzie@shopping.example.net>\r\n",
+        "Subject: Is dinner ready?\r\n",
+        "\r\n",
+        "Hi.\r\n",
+        "\r\n",
+        "We lost the game. Are you hungry yet?\r\n",
+        "\r\n",
+        "Joe.\r\n",
+    );
+
+    fn key() -> RsaKey<Sha256> {
+        RsaKey::from_pem(include_str!("../../private_key.pem")).unwrap()
+    }
+
+    fn signed_message() -> String {
+        let signature = DkimSigner::from_key(key())
+            .domain("example.com")
+            .selector("default")
+            .headers(["From", "To", "Subject"])
+            .sign(MESSAGE.as_bytes())
+            .unwrap();
+        format!("{}{}", signature.to_header(), MESSAGE)
+    }
+
+    fn resolver() -> MemoryResolver {
+        let mut resolver = MemoryResolver::new();
+        resolver.txt_add(
+            "default._domainkey.example.com",
+            key().public_key_record().unwrap().to_string(),
+        );
+        resolver
+    }
+
+    fn verify(resolver: &MemoryResolver, message: &str) -> DkimResult {
+        let outputs = DkimVerifier::new(resolver).verify(message.as_bytes());
+        assert_eq!(outputs.len(), 1);
+        outputs.into_iter().next().unwrap().result
+    }
+
+    #[test]
+    fn round_trip() {
+        assert_eq!(verify(&resolver(), &signed_message()), DkimResult::Pass);
+    }
+
+    #[test]
+    fn tampered_body() {
+        let message = signed_message().replace("hungry", "thirsty");
+        assert_eq!(
+            verify(&resolver(), &message),
+            DkimResult::Fail(Error::FailedBodyHashMatch)
+        );
+    }
+
+    #[test]
+    fn tampered_header() {
+        let message = signed_message().replace("Is dinner ready?", "Is lunch ready?");
+        assert_eq!(
+            verify(&resolver(), &message),
+            DkimResult::Fail(Error::FailedVerification)
+        );
+    }
+
+    #[test]
+    fn revoked_key() {
+        let mut resolver = MemoryResolver::new();
+        resolver.txt_add("default._domainkey.example.com", "v=DKIM1; k=rsa; p=");
+        assert_eq!(
+            verify(&resolver, &signed_message()),
+            DkimResult::PermError(Error::RevokedPublicKey)
+        );
+    }
+
+    #[test]
+    fn missing_key() {
+        assert_eq!(
+            verify(&MemoryResolver::new(), &signed_message()),
+            DkimResult::PermError(Error::DnsRecordNotFound)
+        );
+    }
+
+    #[test]
+    fn resolver_error() {
+        let mut resolver = MemoryResolver::new();
+        let err = Error::DnsError("timeout".to_string());
+        resolver.txt_error("default._domainkey.example.com", err.clone());
+        assert_eq!(
+            verify(&resolver, &signed_message()),
+            DkimResult::TempError(err)
+        );
+    }
+
+    #[test]
+    fn expiration() {
+        let signature = DkimSigner::from_key(key())
+            .domain("example.com")
+            .selector("default")
+            .headers(["From"])
+            .expiration(Duration::from_secs(60))
+            .sign_with_clock(MESSAGE.as_bytes(), &FixedClock(1000))
+            .unwrap();
+        assert_eq!((signature.t, signature.x), (1000, 1060));
+        let message = format!("{}{}", signature.to_header(), MESSAGE);
+        assert!(message.contains(" t=1000; x=1060;"));
+
+        let resolver = resolver();
+        let verifier = DkimVerifier::new(&resolver);
+        for (now, result) in [
+            (1000, DkimResult::Pass),
+            (1060, DkimResult::Pass),
+            (1061, DkimResult::Neutral(Error::SignatureExpired)),
+        ] {
+            let outputs = verifier.verify_with_clock(message.as_bytes(), &FixedClock(now));
+            assert_eq!(outputs[0].result, result, "{now}");
+        }
+    }
+
+    #[test]
+    fn no_timestamp() {
+        let signature = DkimSigner::from_key(key())
+            .domain("example.com")
+            .selector("default")
+            .headers(["From"])
+            .timestamp(false)
+            .sign_with_clock(MESSAGE.as_bytes(), &FixedClock(1000))
+            .unwrap();
+        assert_eq!((signature.t, signature.x), (0, 0));
+        assert!(!signature.to_header().contains("t="));
+    }
+
+    #[test]
+    fn copied_headers() {
+        let subject = "Dinner | lunch; a=b  \u{e9}t\u{e9}";
+        let message = MESSAGE.replace("Is dinner ready?", subject);
+        let signature = DkimSigner::from_key(key())
+            .domain("example.com")
+            .selector("default")
+            .headers(["From", "To", "Subject"])
+            .copy_headers(true)
+            .sign(message.as_bytes())
+            .unwrap();
+        let header = signature.to_header();
+        assert!(header
+            .replace("\r\n\t", "")
+            .contains("Subject:Dinner=20=7C=20lunch=3B=20a=3Db=20=20=C3=A9t=C3=A9"));
+
+        // The copied values survive encoding and parsing unchanged
+        let parsed: Signature = header.parse().unwrap();
+        assert_eq!(parsed.z, signature.z);
+        assert!(parsed
+            .z
+            .contains(&("Subject".to_string(), subject.to_string())));
+
+        let received = |message: &str| {
+            let outputs =
+                DkimVerifier::new(resolver()).verify(format!("{header}{message}").as_bytes());
+            let output = outputs.into_iter().next().unwrap();
+            let signature = output.signature.unwrap();
+            (
+                output.result,
+                signature.diff_copied_headers(message.as_bytes()),
+            )
+        };
+        assert_eq!(received(&message), (DkimResult::Pass, vec![]));
+
+        // Whitespace changes only count with simple canonicalization
+        let (result, diff) = received(&message.replace("a=b  ", "a=b "));
+        assert_eq!(result, DkimResult::Pass);
+        assert!(diff.is_empty());
+
+        let (result, diff) = received(&message.replace("lunch", "brunch"));
+        assert_eq!(result, DkimResult::Fail(Error::FailedVerification));
+        assert_eq!(
+            diff,
+            [HeaderDiff::Modified {
+                name: "Subject".to_string(),
+                signed: subject.to_string(),
+                received: subject.replace("lunch", "brunch"),
+            }]
+        );
+
+        let (_, diff) =
+            received(&message.replace("To: Suzie Q <suzie@shopping.example.net>\r\n", ""));
+        assert_eq!(
+            diff,
+            [HeaderDiff::Removed {
+                name: "To".to_string(),
+                signed: "Suzie Q <suzie@shopping.example.net>".to_string(),
+            }]
+        );
+
+        let (_, diff) = received(&format!("To: eve@example.org\r\n{message}"));
+        assert_eq!(
+            diff,
+            [HeaderDiff::Added {
+                name: "To".to_string(),
+                received: "eve@example.org".to_string(),
+            }]
+        );
+    }
+}
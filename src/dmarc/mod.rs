@@ -0,0 +1,115 @@
+pub mod parse;
+pub mod psl;
+pub mod verify;
+
+use crate::Error;
+use std::collections::HashSet;
+
+/// A DMARC policy record (RFC 7489, section 6.3), published as a TXT record
+/// at `_dmarc.<domain>`, such as `v=DMARC1; p=reject; rua=mailto:d@example.com`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DmarcRecord {
+    /// The policy requested for the domain.
+    pub p: Policy,
+    /// The policy requested for subdomains, defaulting to `p`.
+    pub sp: Option<Policy>,
+    /// The percentage of failing messages the policy applies to.
+    pub pct: u8,
+    pub adkim: Alignment,
+    pub aspf: Alignment,
+    /// Where aggregate reports are sent.
+    pub rua: Vec<ReportUri>,
+    /// Where failure reports are sent.
+    pub ruf: Vec<ReportUri>,
+    /// When failure reports are generated.
+    pub fo: Vec<FailureOption>,
+    /// The requested interval between aggregate reports, in seconds.
+    pub ri: u32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Policy {
+    #[default]
+    None,
+    Quarantine,
+    Reject,
+}
+
+/// How closely an authenticated domain has to match the `From` domain.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Alignment {
+    /// Both domains share the same organizational domain.
+    #[default]
+    Relaxed,
+    /// Both domains are identical.
+    Strict,
+}
+
+/// A reporting address, such as `mailto:dmarc@example.com!10m`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ReportUri {
+    pub uri: String,
+    /// The maximum report size in bytes.
+    pub max_size: Option<u64>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FailureOption {
+    /// Report when all authentication mechanisms fail to produce an aligned pass (`0`).
+    All,
+    /// Report when any authentication mechanism fails to produce an aligned pass (`1`).
+    Any,
+    /// Report when a DKIM signature fails, regardless of alignment (`d`).
+    Dkim,
+    /// Report when SPF fails, regardless of alignment (`s`).
+    Spf,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DmarcResult {
+    /// The `From` domain publishes no DMARC record.
+    None,
+    Pass,
+    Fail,
+    TempError(Error),
+    PermError(Error),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DmarcOutput {
+    pub result: DmarcResult,
+    /// The domain of the `From` address.
+    pub domain: String,
+    /// The domain whose record was applied: the `From` domain or its
+    /// organizational domain.
+    pub policy_domain: String,
+    pub record: Option<DmarcRecord>,
+    /// Whether a valid DKIM signature has a `d=` aligned with the `From` domain.
+    pub dkim_aligned: bool,
+    /// Whether SPF passed for a `MAIL FROM` domain aligned with the `From` domain.
+    pub spf_aligned: bool,
+    /// The policy the domain owner requested, `p=` or, for subdomains, `sp=`.
+    pub policy: Policy,
+    /// The policy to apply to the message: `None` unless DMARC failed and the
+    /// message was selected by `pct=`.
+    pub disposition: Policy,
+}
+
+/// The Public Suffix List (<https://publicsuffix.org>), used to find the
+/// organizational domain of a domain (RFC 7489, section 3.2).
+#[derive(Debug, Clone, Default)]
+pub struct PublicSuffixList {
+    pub(crate) rules: HashSet<String>,
+    /// The parent domains of `*.` wildcard rules.
+    pub(crate) wildcards: HashSet<String>,
+    /// The domains of `!` exception rules.
+    pub(crate) exceptions: HashSet<String>,
+}
+
+/// Evaluates the DMARC policy of the `From` domain of a message (RFC 7489),
+/// given its DKIM and SPF results.
+#[derive(Debug, Clone)]
+pub struct DmarcVerifier<R> {
+    pub(crate) resolver: R,
+    pub(crate) public_suffixes: Option<PublicSuffixList>,
+}
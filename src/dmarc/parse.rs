@@ -0,0 +1,303 @@
+use super::{Alignment, DmarcRecord, FailureOption, Policy, ReportUri};
+use crate::{
+    common::parse::{split_list, TagList},
+    dkim::parse::{parse_number, tag_name},
+    Error,
+};
+use std::str::FromStr;
+
+impl DmarcRecord {
+    /// Parses a DMARC record starting with `v=DMARC1`. Unknown tags and
+    /// invalid reporting URIs are ignored. A record with a missing or invalid
+    /// `p=` or `sp=` is read as `p=none` when it requests aggregate reports
+    /// (RFC 7489, section 6.6.3).
+    pub fn parse(record: &[u8]) -> crate::Result<Self> {
+        let mut result = DmarcRecord {
+            p: Policy::None,
+            sp: None,
+            pct: 100,
+            adkim: Alignment::Relaxed,
+            aspf: Alignment::Relaxed,
+            rua: Vec::new(),
+            ruf: Vec::new(),
+            fo: vec![FailureOption::All],
+            ri: 86400,
+        };
+        let mut policy = None;
+        let mut policy_error = None;
+        let mut seen_tags: Vec<&[u8]> = Vec::new();
+
+        for (pos, tag) in TagList::new(record).enumerate() {
+            let (name, value) = tag?;
+            if pos == 0 && (name != b"v" || value != b"DMARC1") {
+                return Err(Error::UnsupportedVersion);
+            } else if seen_tags.contains(&name) {
+                return Err(Error::DuplicateTag(tag_name(name)));
+            }
+            seen_tags.push(name);
+
+            match name {
+                b"p" => match Policy::parse(value) {
+                    Some(p) => policy = Some(p),
+                    None => policy_error = Some(Error::InvalidTag(tag_name(name))),
+                },
+                b"sp" => match Policy::parse(value) {
+                    Some(sp) => result.sp = Some(sp),
+                    None => policy_error = Some(Error::InvalidTag(tag_name(name))),
+                },
+                b"pct" => {
+                    result.pct = parse_number(name, value)
+                        .ok()
+                        .filter(|pct| *pct <= 100)
+                        .ok_or_else(|| Error::InvalidTag(tag_name(name)))?
+                        as u8;
+                }
+                b"adkim" => result.adkim = Alignment::parse(name, value)?,
+                b"aspf" => result.aspf = Alignment::parse(name, value)?,
+                b"rua" => result.rua = ReportUri::parse_list(value),
+                b"ruf" => result.ruf = ReportUri::parse_list(value),
+                b"fo" => {
+                    result.fo = split_list(value, b':')
+                        .map(FailureOption::parse)
+                        .collect::<Option<_>>()
+                        .ok_or_else(|| Error::InvalidTag(tag_name(name)))?;
+                }
+                b"ri" => {
+                    result.ri = u32::try_from(parse_number(name, value)?)
+                        .map_err(|_| Error::InvalidTag(tag_name(name)))?;
+                }
+                _ => (),
+            }
+        }
+
+        if seen_tags.is_empty() {
+            return Err(Error::UnsupportedVersion);
+        }
+        match (policy, policy_error) {
+            (Some(p), None) => result.p = p,
+            // Domains asking for reports get them even with a broken policy
+            _ if !result.rua.is_empty() => result.sp = None,
+            (_, Some(err)) => return Err(err),
+            (None, None) => return Err(Error::MissingTag("p".to_string())),
+        }
+
+        Ok(result)
+    }
+
+    /// Whether a TXT record is a DMARC record, selected by its version.
+    pub(crate) fn is_dmarc_record(record: &[u8]) -> bool {
+        record
+            .strip_prefix(b"v=DMARC1")
+            .is_some_and(|rest| matches!(rest.trim_ascii_start().first(), None | Some(b';')))
+    }
+}
+
+impl FromStr for DmarcRecord {
+    type Err = Error;
+
+    fn from_str(s: &str) -> crate::Result<Self> {
+        DmarcRecord::parse(s.as_bytes())
+    }
+}
+
+impl Policy {
+    fn parse(value: &[u8]) -> Option<Self> {
+        if value.eq_ignore_ascii_case(b"none") {
+            Some(Policy::None)
+        } else if value.eq_ignore_ascii_case(b"quarantine") {
+            Some(Policy::Quarantine)
+        } else if value.eq_ignore_ascii_case(b"reject") {
+            Some(Policy::Reject)
+        } else {
+            None
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Policy::None => "none",
+            Policy::Quarantine => "quarantine",
+            Policy::Reject => "reject",
+        }
+    }
+}
+
+impl Alignment {
+    fn parse(name: &[u8], value: &[u8]) -> crate::Result<Self> {
+        if value.eq_ignore_ascii_case(b"r") {
+            Ok(Alignment::Relaxed)
+        } else if value.eq_ignore_ascii_case(b"s") {
+            Ok(Alignment::Strict)
+        } else {
+            Err(Error::InvalidTag(tag_name(name)))
+        }
+    }
+}
+
+impl FailureOption {
+    fn parse(value: &[u8]) -> Option<Self> {
+        match value {
+            b"0" => Some(FailureOption::All),
+            b"1" => Some(FailureOption::Any),
+            b"d" | b"D" => Some(FailureOption::Dkim),
+            b"s" | b"S" => Some(FailureOption::Spf),
+            _ => None,
+        }
+    }
+}
+
+impl ReportUri {
+    /// Parses a comma-separated list of URIs with an optional `!` size limit,
+    /// skipping the invalid ones.
+    fn parse_list(value: &[u8]) -> Vec<Self> {
+        split_list(value, b',')
+            .filter_map(|uri| std::str::from_utf8(uri).ok())
+            .filter_map(ReportUri::parse)
+            .collect()
+    }
+
+    fn parse(value: &str) -> Option<Self> {
+        let (uri, max_size) = match value.rsplit_once('!') {
+            Some((uri, size)) => (uri, Some(parse_size(size)?)),
+            None => (value, None),
+        };
+        let (scheme, rest) = uri.split_once(':')?;
+        (scheme
+            .bytes()
+            .all(|ch| ch.is_ascii_alphanumeric() || b"+-.".contains(&ch))
+            && scheme.starts_with(|ch: char| ch.is_ascii_alphabetic())
+            && !rest.is_empty())
+        .then(|| ReportUri {
+            uri: uri.to_string(),
+            max_size,
+        })
+    }
+}
+
+/// Parses a report size such as `10m`, in bytes.
+fn parse_size(size: &str) -> Option<u64> {
+    let (digits, multiplier) = match size.as_bytes().last()?.to_ascii_lowercase() {
+        b'k' => (&size[..size.len() - 1], 1 << 10),
+        b'm' => (&size[..size.len() - 1], 1 << 20),
+        b'g' => (&size[..size.len() - 1], 1 << 30),
+        b't' => (&size[..size.len() - 1], 1 << 40),
+        _ => (size, 1),
+    };
+    if digits.is_empty() || !digits.bytes().all(|ch| ch.is_ascii_digit()) {
+        return None;
+    }
+    digits.parse::<u64>().ok()?.checked_mul(multiplier)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        dmarc::{Alignment, FailureOption, Policy, ReportUri},
+        DmarcRecord, Error,
+    };
+
+    #[test]
+    fn parse_record() {
+        let record: DmarcRecord = concat!(
+            "v=DMARC1; p=reject; sp=quarantine; pct=20; adkim=s; aspf=r; ",
+            "rua=mailto:dmarc@example.com!10m, invalid, https://example.com/report; ",
+            "ruf=mailto:forensic@example.com; fo=1:d; ri=3600; unknown=tag"
+        )
+        .parse()
+        .unwrap();
+        assert_eq!(
+            record,
+            DmarcRecord {
+                p: Policy::Reject,
+                sp: Some(Policy::Quarantine),
+                pct: 20,
+                adkim: Alignment::Strict,
+                aspf: Alignment::Relaxed,
+                rua: vec![
+                    ReportUri {
+                        uri: "mailto:dmarc@example.com".to_string(),
+                        max_size: Some(10 << 20),
+                    },
+                    ReportUri {
+                        uri: "https://example.com/report".to_string(),
+                        max_size: None,
+                    },
+                ],
+                ruf: vec![ReportUri {
+                    uri: "mailto:forensic@example.com".to_string(),
+                    max_size: None,
+                }],
+                fo: vec![FailureOption::Any, FailureOption::Dkim],
+                ri: 3600,
+            }
+        );
+
+        let record: DmarcRecord = "v=DMARC1; p=none".parse().unwrap();
+        assert_eq!(
+            (record.sp, record.pct, record.adkim, record.fo, record.ri),
+            (
+                None,
+                100,
+                Alignment::Relaxed,
+                vec![FailureOption::All],
+                86400
+            )
+        );
+    }
+
+    #[test]
+    fn policy_fallback() {
+        // A missing or invalid policy is read as p=none when rua= is present
+        for record in [
+            "v=DMARC1; rua=mailto:dmarc@example.com",
+            "v=DMARC1; p=block; rua=mailto:dmarc@example.com",
+            "v=DMARC1; p=reject; sp=block; rua=mailto:dmarc@example.com",
+        ] {
+            let record: DmarcRecord = record.parse().unwrap();
+            assert_eq!((record.p, record.sp), (Policy::None, None));
+            assert_eq!(record.rua.len(), 1);
+        }
+
+        assert_eq!(
+            "v=DMARC1; pct=50".parse::<DmarcRecord>(),
+            Err(Error::MissingTag("p".to_string()))
+        );
+        assert_eq!(
+            "v=DMARC1; p=block".parse::<DmarcRecord>(),
+            Err(Error::InvalidTag("p".to_string()))
+        );
+        assert_eq!(
+            "v=DMARC1; p=none; sp=block; rua=invalid".parse::<DmarcRecord>(),
+            Err(Error::InvalidTag("sp".to_string()))
+        );
+    }
+
+    #[test]
+    fn parse_errors() {
+        for (record, err) in [
+            ("p=none; v=DMARC1", Error::UnsupportedVersion),
+            ("v=DMARC2; p=none", Error::UnsupportedVersion),
+            (
+                "v=DMARC1; p=none; p=reject",
+                Error::DuplicateTag("p".into()),
+            ),
+            ("v=DMARC1; p=none; pct=101", Error::InvalidTag("pct".into())),
+            ("v=DMARC1; p=none; pct=-1", Error::InvalidTag("pct".into())),
+            (
+                "v=DMARC1; p=none; adkim=x",
+                Error::InvalidTag("adkim".into()),
+            ),
+            ("v=DMARC1; p=none; fo=2", Error::InvalidTag("fo".into())),
+        ] {
+            assert_eq!(record.parse::<DmarcRecord>(), Err(err), "{record}");
+        }
+        for pct in ["0", "100"] {
+            let record = format!("v=DMARC1; p=none; pct={pct}");
+            assert!(record.parse::<DmarcRecord>().is_ok());
+        }
+        assert!(DmarcRecord::is_dmarc_record(b"v=DMARC1; p=none"));
+        assert!(DmarcRecord::is_dmarc_record(b"v=DMARC1"));
+        assert!(!DmarcRecord::is_dmarc_record(b"v=DMARC10; p=none"));
+        assert!(!DmarcRecord::is_dmarc_record(b"v=spf1 -all"));
+    }
+}
@@ -0,0 +1,248 @@
+use super::PublicSuffixList;
+use std::{collections::HashSet, sync::OnceLock};
+
+/// The Public Suffix List bundled with the crate. Unlike the rest of the
+/// crate, the list is licensed under the Mozilla Public License 2.0.
+const PUBLIC_SUFFIX_LIST: &str = include_str!("public_suffix_list.dat");
+
+impl PublicSuffixList {
+    /// Parses a list in the `public_suffix_list.dat` format. Comments and
+    /// anything after the first whitespace of a line are ignored.
+    /// Internationalized rules match both their Unicode and their `xn--` form.
+    pub fn parse(list: &str) -> Self {
+        let mut result = PublicSuffixList::default();
+        for line in list.lines() {
+            let Some(rule) = line.split_whitespace().next() else {
+                continue;
+            };
+            if rule.starts_with("//") {
+                continue;
+            }
+            let rule = rule.trim_end_matches('.').to_lowercase();
+            if let Some(exception) = rule.strip_prefix('!') {
+                insert(&mut result.exceptions, exception);
+            } else if let Some(parent) = rule.strip_prefix("*.") {
+                insert(&mut result.wildcards, parent);
+            } else {
+                insert(&mut result.rules, &rule);
+            }
+        }
+        result
+    }
+
+    /// Returns the list bundled with the crate, which is parsed on first use.
+    pub fn bundled() -> &'static Self {
+        static BUNDLED: OnceLock<PublicSuffixList> = OnceLock::new();
+        BUNDLED.get_or_init(|| PublicSuffixList::parse(PUBLIC_SUFFIX_LIST))
+    }
+
+    /// Returns the public suffix of a lowercase domain name, such as `co.uk`
+    /// for `mail.example.co.uk`. Unlisted top-level domains are public
+    /// suffixes.
+    pub fn public_suffix<'x>(&self, domain: &'x str) -> &'x str {
+        let domain = domain.trim_end_matches('.');
+        let mut suffix = domain;
+        loop {
+            let parent = suffix.split_once('.').map(|(_, parent)| parent);
+            if self.exceptions.contains(suffix) {
+                return parent.unwrap_or(suffix);
+            } else if self.rules.contains(suffix)
+                || parent.is_some_and(|parent| self.wildcards.contains(parent))
+            {
+                return suffix;
+            }
+            match parent {
+                Some(parent) => suffix = parent,
+                None => return suffix,
+            }
+        }
+    }
+
+    /// Returns the organizational domain of a lowercase domain name: its
+    /// public suffix and one more label (RFC 7489, section 3.2). A domain
+    /// that is itself a public suffix is its own organizational domain.
+    pub fn organizational_domain<'x>(&self, domain: &'x str) -> &'x str {
+        let domain = domain.trim_end_matches('.');
+        let suffix = self.public_suffix(domain);
+        match domain
+            .strip_suffix(suffix)
+            .and_then(|prefix| prefix.strip_suffix('.'))
+        {
+            Some(prefix) => {
+                let start = prefix.rfind('.').map_or(0, |pos| pos + 1);
+                &domain[start..]
+            }
+            None => domain,
+        }
+    }
+}
+
+/// Adds a domain to `set`, along with its ASCII form when it has
+/// internationalized labels.
+fn insert(set: &mut HashSet<String>, domain: &str) {
+    if !domain.is_ascii() {
+        let labels = domain
+            .split('.')
+            .map(|label| {
+                if label.is_ascii() {
+                    Some(label.to_string())
+                } else {
+                    punycode(label).map(|label| format!("xn--{label}"))
+                }
+            })
+            .collect::<Option<Vec<_>>>();
+        if let Some(labels) = labels {
+            set.insert(labels.join("."));
+        }
+    }
+    set.insert(domain.to_string());
+}
+
+/// Encodes a label with Punycode (RFC 3492, section 6.3).
+fn punycode(label: &str) -> Option<String> {
+    const BASE: u32 = 36;
+    const T_MIN: u32 = 1;
+    const T_MAX: u32 = 26;
+
+    let input = label.chars().map(u32::from).collect::<Vec<_>>();
+    let mut output = label.chars().filter(char::is_ascii).collect::<String>();
+    let basic = output.len() as u32;
+    if basic > 0 {
+        output.push('-');
+    }
+
+    let (mut n, mut delta, mut bias, mut handled) = (0x80, 0u32, 72, basic);
+    while (handled as usize) < input.len() {
+        let m = input.iter().copied().filter(|&c| c >= n).min()?;
+        delta = delta.checked_add((m - n).checked_mul(handled + 1)?)?;
+        n = m;
+        for &c in &input {
+            if c < n {
+                delta = delta.checked_add(1)?;
+            } else if c == n {
+                let mut q = delta;
+                let mut k = BASE;
+                loop {
+                    let t = k.saturating_sub(bias).clamp(T_MIN, T_MAX);
+                    if q < t {
+                        break;
+                    }
+                    output.push(digit(t + (q - t) % (BASE - t)));
+                    q = (q - t) / (BASE - t);
+                    k += BASE;
+                }
+                output.push(digit(q));
+                bias = adapt(delta, handled + 1, handled == basic);
+                delta = 0;
+                handled += 1;
+            }
+        }
+        delta = delta.checked_add(1)?;
+        n += 1;
+    }
+    Some(output)
+}
+
+fn adapt(delta: u32, points: u32, first: bool) -> u32 {
+    let mut delta = if first { delta / 700 } else { delta / 2 };
+    delta += delta / points;
+    let mut k = 0;
+    while delta > ((36 - 1) * 26) / 2 {
+        delta /= 36 - 1;
+        k += 36;
+    }
+    k + (36 * delta) / (delta + 38)
+}
+
+fn digit(d: u32) -> char {
+    match d {
+        0..=25 => char::from(b'a' + d as u8),
+        _ => char::from(b'0' + (d - 26) as u8),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::punycode;
+    use crate::dmarc::PublicSuffixList;
+
+    #[test]
+    fn organizational_domain() {
+        let list = PublicSuffixList::bundled();
+        for (domain, suffix, organizational_domain) in [
+            ("example.com", "com", "example.com"),
+            ("mail.example.com", "com", "example.com"),
+            ("a.b.example.co.uk", "co.uk", "example.co.uk"),
+            ("co.uk", "co.uk", "co.uk"),
+            ("com", "com", "com"),
+            ("example.com.", "com", "example.com"),
+            // Unlisted top-level domains are public suffixes
+            ("mail.example.unlisted", "unlisted", "example.unlisted"),
+            // *.kawasaki.jp with the exception !city.kawasaki.jp
+            ("a.b.kawasaki.jp", "b.kawasaki.jp", "a.b.kawasaki.jp"),
+            ("mail.a.b.kawasaki.jp", "b.kawasaki.jp", "a.b.kawasaki.jp"),
+            ("mail.city.kawasaki.jp", "kawasaki.jp", "city.kawasaki.jp"),
+        ] {
+            assert_eq!(list.public_suffix(domain), suffix, "{domain}");
+            assert_eq!(
+                list.organizational_domain(domain),
+                organizational_domain,
+                "{domain}"
+            );
+        }
+    }
+
+    #[test]
+    fn internationalized_domains() {
+        let list = PublicSuffixList::bundled();
+        for (domain, organizational_domain) in [
+            // 公司.cn, with both labels encoded or only the first
+            ("mail.example.公司.cn", "example.公司.cn"),
+            ("mail.example.xn--55qx5d.cn", "example.xn--55qx5d.cn"),
+            // 個人.香港
+            ("mail.example.個人.香港", "example.個人.香港"),
+            (
+                "mail.example.xn--gmqw5a.xn--j6w193g",
+                "example.xn--gmqw5a.xn--j6w193g",
+            ),
+            // рф
+            ("mail.example.xn--p1ai", "example.xn--p1ai"),
+        ] {
+            assert_eq!(
+                list.organizational_domain(domain),
+                organizational_domain,
+                "{domain}"
+            );
+        }
+
+        let list = PublicSuffixList::parse("*.bücher.example\n!info.bücher.example\n");
+        assert_eq!(
+            list.public_suffix("a.b.xn--bcher-kva.example"),
+            "b.xn--bcher-kva.example"
+        );
+        assert_eq!(
+            list.public_suffix("a.info.xn--bcher-kva.example"),
+            "xn--bcher-kva.example"
+        );
+    }
+
+    #[test]
+    fn punycode_labels() {
+        // RFC 3492, section 7.1
+        for (label, encoded) in [
+            ("bücher", "bcher-kva"),
+            ("münchen", "mnchen-3ya"),
+            ("公司", "55qx5d"),
+            ("香港", "j6w193g"),
+            ("рф", "p1ai"),
+            ("他们为什么不说中文", "ihqwcrb4cv8a8dqg056pqjye"),
+            ("pročprostěnemluvíčesky", "Proprostnemluvesky-uyb24dma41a"),
+        ] {
+            assert_eq!(
+                punycode(label).as_deref(),
+                Some(encoded.to_lowercase().as_str()),
+                "{label}"
+            );
+        }
+    }
+}
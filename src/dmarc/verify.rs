@@ -0,0 +1,404 @@
+use super::{
+    Alignment, DmarcOutput, DmarcRecord, DmarcResult, DmarcVerifier, Policy, PublicSuffixList,
+};
+use crate::{
+    common::{headers::HeaderIterator, parse::parse_address, resolver::Resolver},
+    dkim::{DkimOutput, DkimResult},
+    spf::{SpfIdentity, SpfOutput, SpfResult},
+    Error,
+};
+use rand_core::{OsRng, RngCore};
+
+impl<R: Resolver> DmarcVerifier<R> {
+    pub fn new(resolver: R) -> Self {
+        DmarcVerifier {
+            resolver,
+            public_suffixes: None,
+        }
+    }
+
+    /// Replaces the bundled Public Suffix List used to find organizational
+    /// domains.
+    pub fn public_suffix_list(mut self, public_suffixes: PublicSuffixList) -> Self {
+        self.public_suffixes = Some(public_suffixes);
+        self
+    }
+
+    /// Evaluates the DMARC policy of the `From` domain of `message`, given
+    /// the DKIM results of the message and the SPF result of its `MAIL FROM`.
+    pub fn verify(&self, message: &[u8], dkim: &[DkimOutput], spf: &SpfOutput) -> DmarcOutput {
+        self.verify_with_rng(message, dkim, spf, &mut OsRng)
+    }
+
+    /// Evaluates the DMARC policy of `message`, using `rng` to select the
+    /// failing messages a `pct=` below 100 applies to.
+    pub fn verify_with_rng(
+        &self,
+        message: &[u8],
+        dkim: &[DkimOutput],
+        spf: &SpfOutput,
+        rng: &mut impl RngCore,
+    ) -> DmarcOutput {
+        let mut output = DmarcOutput {
+            result: DmarcResult::None,
+            domain: String::new(),
+            policy_domain: String::new(),
+            record: None,
+            dkim_aligned: false,
+            spf_aligned: false,
+            policy: Policy::None,
+            disposition: Policy::None,
+        };
+
+        // Messages with several From headers or none are not evaluated
+        let mut from_headers = HeaderIterator::new(message)
+            .filter(|(name, _)| name.trim_ascii_end().eq_ignore_ascii_case(b"From"));
+        output.domain = match (from_headers.next(), from_headers.next()) {
+            (Some((_, value)), None) => match parse_address(value) {
+                Some((_, domain)) => domain,
+                None => {
+                    output.result = DmarcResult::PermError(Error::MissingFrom);
+                    return output;
+                }
+            },
+            (Some(_), Some(_)) => {
+                output.result = DmarcResult::PermError(Error::MalformedMessage);
+                return output;
+            }
+            (None, _) => {
+                output.result = DmarcResult::PermError(Error::MissingFrom);
+                return output;
+            }
+        };
+
+        // Fall back to the record of the organizational domain
+        let organizational_domain = self.organizational_domain(&output.domain).to_string();
+        let mut policy_domain = output.domain.clone();
+        let mut record = self.fetch_record(&policy_domain);
+        if matches!(record, Ok(None)) && organizational_domain != output.domain {
+            policy_domain = organizational_domain;
+            record = self.fetch_record(&policy_domain);
+        }
+        let record = match record {
+            Ok(Some(record)) => record,
+            Ok(None) => return output,
+            Err(result) => {
+                output.result = result;
+                return output;
+            }
+        };
+
+        output.policy = match record.sp {
+            Some(sp) if policy_domain != output.domain => sp,
+            _ => record.p,
+        };
+        output.dkim_aligned = dkim.iter().any(|dkim| {
+            dkim.result == DkimResult::Pass
+                && dkim.signature.as_ref().is_some_and(|signature| {
+                    self.is_aligned(&signature.d, &output.domain, record.adkim)
+                })
+        });
+        output.spf_aligned = spf.result == SpfResult::Pass
+            && spf.identity == SpfIdentity::MailFrom
+            && self.is_aligned(&spf.domain, &output.domain, record.aspf);
+
+        if output.dkim_aligned || output.spf_aligned {
+            output.result = DmarcResult::Pass;
+        } else {
+            output.result = DmarcResult::Fail;
+            // Messages not selected by pct= get the next less strict policy
+            output.disposition = if record.pct >= 100 || rng.next_u32() % 100 < record.pct as u32 {
+                output.policy
+            } else {
+                match output.policy {
+                    Policy::Reject => Policy::Quarantine,
+                    Policy::Quarantine | Policy::None => Policy::None,
+                }
+            };
+        }
+        output.policy_domain = policy_domain;
+        output.record = Some(record);
+
+        output
+    }
+
+    /// Returns the organizational domain of a lowercase domain name.
+    pub fn organizational_domain<'x>(&self, domain: &'x str) -> &'x str {
+        self.public_suffixes
+            .as_ref()
+            .unwrap_or_else(|| PublicSuffixList::bundled())
+            .organizational_domain(domain)
+    }
+
+    /// Fetches the DMARC record of `domain`. A domain with no DMARC record,
+    /// more than one or one that cannot be parsed has no policy (RFC 7489,
+    /// section 6.6.3).
+    fn fetch_record(&self, domain: &str) -> Result<Option<DmarcRecord>, DmarcResult> {
+        let records = match self.resolver.txt_lookup(&format!("_dmarc.{domain}")) {
+            Ok(records) => records,
+            Err(Error::DnsRecordNotFound) => return Ok(None),
+            Err(err) => return Err(DmarcResult::TempError(err)),
+        };
+        let mut records = records
+            .iter()
+            .filter(|record| DmarcRecord::is_dmarc_record(record));
+        match (records.next(), records.next()) {
+            (Some(record), None) => Ok(DmarcRecord::parse(record).ok()),
+            _ => Ok(None),
+        }
+    }
+
+    /// Whether an authenticated domain is aligned with the `From` domain
+    /// (RFC 7489, section 3.1).
+    fn is_aligned(&self, domain: &str, from_domain: &str, alignment: Alignment) -> bool {
+        let domain = domain.trim_end_matches('.').to_ascii_lowercase();
+        domain == from_domain
+            || (alignment == Alignment::Relaxed
+                && self.organizational_domain(&domain) == self.organizational_domain(from_domain))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        dkim::{DkimOutput, DkimResult, Signature},
+        spf::{SpfIdentity, SpfOutput, SpfResult},
+        DmarcResult, DmarcVerifier, Error, MemoryResolver, Policy,
+    };
+    use rand_core::{impls, RngCore};
+
+    const MESSAGE: &[u8] = b"From: Joe <joe@mail.example.com>\r\nSubject: Hi\r\n\r\nHello\r\n";
+
+    /// Returns the same number every time, to select messages with `pct=`.
+    struct FixedRng(u32);
+
+    impl RngCore for FixedRng {
+        fn next_u32(&mut self) -> u32 {
+            self.0
+        }
+
+        fn next_u64(&mut self) -> u64 {
+            self.0.into()
+        }
+
+        fn fill_bytes(&mut self, dest: &mut [u8]) {
+            impls::fill_bytes_via_next(self, dest)
+        }
+
+        fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), rand_core::Error> {
+            self.fill_bytes(dest);
+            Ok(())
+        }
+    }
+
+    fn verifier(record: &str) -> DmarcVerifier<MemoryResolver> {
+        let mut resolver = MemoryResolver::new();
+        resolver.txt_add("_dmarc.example.com", record);
+        DmarcVerifier::new(resolver)
+    }
+
+    fn dkim(result: DkimResult, d: &str) -> DkimOutput {
+        DkimOutput {
+            result,
+            signature: Some(Signature {
+                d: d.to_string(),
+                ..Default::default()
+            }),
+        }
+    }
+
+    fn spf(result: SpfResult, identity: SpfIdentity, domain: &str) -> SpfOutput {
+        SpfOutput {
+            result,
+            identity,
+            domain: domain.to_string(),
+            sender: format!("bounce@{domain}"),
+            explanation: None,
+        }
+    }
+
+    fn no_spf() -> SpfOutput {
+        spf(SpfResult::None, SpfIdentity::MailFrom, "other.example")
+    }
+
+    #[test]
+    fn dkim_alignment() {
+        for (record, d, aligned) in [
+            ("v=DMARC1; p=reject", "mail.example.com", true),
+            ("v=DMARC1; p=reject", "example.com", true),
+            ("v=DMARC1; p=reject", "other.mail.example.com", true),
+            ("v=DMARC1; p=reject", "Example.COM.", true),
+            ("v=DMARC1; p=reject", "example.org", false),
+            ("v=DMARC1; p=reject; adkim=s", "mail.example.com", true),
+            ("v=DMARC1; p=reject; adkim=s", "example.com", false),
+        ] {
+            let output = verifier(record).verify(
+                MESSAGE,
+                &[
+                    dkim(DkimResult::Fail(Error::FailedBodyHashMatch), "example.com"),
+                    dkim(DkimResult::Pass, d),
+                ],
+                &no_spf(),
+            );
+            assert_eq!(output.dkim_aligned, aligned, "{record} {d}");
+            assert!(!output.spf_aligned);
+            assert_eq!(output.domain, "mail.example.com");
+            assert_eq!(output.policy_domain, "example.com");
+            if aligned {
+                assert_eq!(output.result, DmarcResult::Pass);
+                assert_eq!(output.disposition, Policy::None);
+            } else {
+                assert_eq!(output.result, DmarcResult::Fail);
+                assert_eq!(output.disposition, Policy::Reject);
+            }
+        }
+
+        // Failed signatures are never aligned
+        let output = verifier("v=DMARC1; p=reject").verify(
+            MESSAGE,
+            &[dkim(
+                DkimResult::Fail(Error::FailedBodyHashMatch),
+                "mail.example.com",
+            )],
+            &no_spf(),
+        );
+        assert_eq!(output.result, DmarcResult::Fail);
+    }
+
+    #[test]
+    fn spf_alignment() {
+        for (record, spf, aligned) in [
+            (
+                "v=DMARC1; p=reject",
+                spf(
+                    SpfResult::Pass,
+                    SpfIdentity::MailFrom,
+                    "bounces.example.com",
+                ),
+                true,
+            ),
+            (
+                "v=DMARC1; p=reject; aspf=s",
+                spf(
+                    SpfResult::Pass,
+                    SpfIdentity::MailFrom,
+                    "bounces.example.com",
+                ),
+                false,
+            ),
+            (
+                "v=DMARC1; p=reject; aspf=s",
+                spf(SpfResult::Pass, SpfIdentity::MailFrom, "mail.example.com"),
+                true,
+            ),
+            (
+                "v=DMARC1; p=reject",
+                spf(SpfResult::SoftFail, SpfIdentity::MailFrom, "example.com"),
+                false,
+            ),
+            // Only the MAIL FROM identity is used
+            (
+                "v=DMARC1; p=reject",
+                spf(SpfResult::Pass, SpfIdentity::Helo, "example.com"),
+                false,
+            ),
+        ] {
+            let output = verifier(record).verify(MESSAGE, &[], &spf);
+            assert_eq!(output.spf_aligned, aligned, "{record} {spf:?}");
+            assert_eq!(output.result == DmarcResult::Pass, aligned);
+        }
+    }
+
+    #[test]
+    fn policy_lookup() {
+        // The record of the From domain takes precedence
+        let mut resolver = MemoryResolver::new();
+        resolver.txt_add("_dmarc.example.com", "v=DMARC1; p=reject; sp=quarantine");
+        resolver.txt_add("_dmarc.mail.example.com", "v=DMARC1; p=none");
+        let output = DmarcVerifier::new(&resolver).verify(MESSAGE, &[], &no_spf());
+        assert_eq!(output.policy_domain, "mail.example.com");
+        assert_eq!(output.disposition, Policy::None);
+
+        // Subdomains get sp= from the organizational domain
+        let output = verifier("v=DMARC1; p=reject; sp=quarantine").verify(MESSAGE, &[], &no_spf());
+        assert_eq!(output.policy_domain, "example.com");
+        assert_eq!(output.policy, Policy::Quarantine);
+        assert_eq!(output.disposition, Policy::Quarantine);
+
+        // No record, or an unresolvable one
+        let output = DmarcVerifier::new(MemoryResolver::new()).verify(MESSAGE, &[], &no_spf());
+        assert_eq!(output.result, DmarcResult::None);
+        let mut resolver = MemoryResolver::new();
+        resolver.txt_error(
+            "_dmarc.mail.example.com",
+            Error::DnsError("timeout".to_string()),
+        );
+        let output = DmarcVerifier::new(resolver).verify(MESSAGE, &[], &no_spf());
+        assert!(matches!(output.result, DmarcResult::TempError(_)));
+        let output = verifier("v=DMARC1; p=block").verify(MESSAGE, &[], &no_spf());
+        assert_eq!(output.result, DmarcResult::None);
+        assert_eq!(output.record, None);
+
+        // A record that cannot be used falls back to the organizational domain
+        for record in [
+            "v=DMARC1; p=none; adkim=x",
+            "v=DMARC1; p=none; pct=101",
+            "v=DMARC1; sp=reject",
+        ] {
+            let mut resolver = MemoryResolver::new();
+            resolver.txt_add("_dmarc.example.com", "v=DMARC1; p=reject");
+            resolver.txt_add("_dmarc.mail.example.com", record);
+            let output = DmarcVerifier::new(resolver).verify(MESSAGE, &[], &no_spf());
+            assert_eq!(output.result, DmarcResult::Fail, "{record}");
+            assert_eq!(output.policy_domain, "example.com");
+            assert_eq!(output.disposition, Policy::Reject);
+        }
+
+        // Messages without exactly one From domain are not evaluated
+        let output = verifier("v=DMARC1; p=reject").verify(
+            b"From: a@example.com\r\nFrom: b@example.com\r\n\r\n",
+            &[],
+            &no_spf(),
+        );
+        assert_eq!(
+            output.result,
+            DmarcResult::PermError(Error::MalformedMessage)
+        );
+    }
+
+    #[test]
+    fn pct() {
+        for (record, rng, disposition) in [
+            ("v=DMARC1; p=reject; pct=25", 24, Policy::Reject),
+            ("v=DMARC1; p=reject; pct=25", 25, Policy::Quarantine),
+            ("v=DMARC1; p=quarantine; pct=25", 25, Policy::None),
+            ("v=DMARC1; p=reject; pct=0", 0, Policy::Quarantine),
+            ("v=DMARC1; p=reject; pct=100", 99, Policy::Reject),
+        ] {
+            let output =
+                verifier(record).verify_with_rng(MESSAGE, &[], &no_spf(), &mut FixedRng(rng));
+            assert_eq!(output.result, DmarcResult::Fail);
+            assert_eq!(output.disposition, disposition, "{record} {rng}");
+        }
+    }
+
+    #[test]
+    fn organizational_domain() {
+        let verifier = verifier("v=DMARC1; p=none");
+        assert_eq!(
+            verifier.organizational_domain("mail.example.co.uk"),
+            "example.co.uk"
+        );
+        assert_eq!(
+            verifier.organizational_domain("mail.example.xn--55qx5d.cn"),
+            "example.xn--55qx5d.cn"
+        );
+
+        let verifier =
+            verifier.public_suffix_list(crate::dmarc::PublicSuffixList::parse("example.com\n"));
+        assert_eq!(
+            verifier.organizational_domain("mail.a.example.com"),
+            "a.example.com"
+        );
+    }
+}
@@ -1,45 +1,115 @@
-//! A minimal DKIM signing library for Rust.
+//! A minimal DKIM signing and verification library for Rust.
 
 // Module declarations
+pub mod arc;
+pub mod auth_results;
 pub mod common;
 pub mod dkim;
+pub mod dmarc;
+pub mod spf;
 
 // Re-export the main signer struct and other necessary components.
-pub use common::crypto::{RsaKey, Sha256};
-pub use common::headers::HeaderWriter;
-pub use dkim::{DkimSigner, Signature};
+pub use arc::{ArcOutput, ArcResult, ArcSealer, ArcSet, ArcVerifier, ChainValidation};
+pub use auth_results::{AuthResult, AuthenticationResults, Method, MethodResult};
+pub use common::clock::{Clock, FixedClock, SystemClock};
+pub use common::crypto::{Ed25519Key, RsaKey, Sha256};
+pub use common::headers::{HeaderWriter, IoWriter};
+pub use common::remote::ExternalKey;
+#[cfg(feature = "test-util")]
+pub use common::remote::SigningService;
+pub use common::resolver::{MemoryResolver, Resolver};
+pub use dkim::{
+    DkimOutput, DkimPublicKeyRecord, DkimResult, DkimSigner, DkimSigningContext, DkimVerifier,
+    KeyType, MultiSigner, Signature, SigningTable,
+};
+pub use dmarc::{DmarcOutput, DmarcRecord, DmarcResult, DmarcVerifier, Policy};
+pub use spf::{SpfOutput, SpfRecord, SpfResult, SpfVerifier};
 
 /// A simplified function to sign an email with an RSA-SHA256 DKIM signature.
 ///
+/// The recommended headers present in the message are signed, see
+/// [`dkim::RECOMMENDED_HEADERS`].
+///
 /// # Arguments
 ///
 /// * `email` - The full email content (headers and body) as a string slice.
 /// * `domain` - The signing domain (e.g., "example.com").
 /// * `selector` - The DKIM selector (e.g., "default").
-/// * `private_key` - The RSA private key in PKCS#1 PEM format.
+/// * `private_key` - The RSA private key in PKCS#1 or PKCS#8 PEM format.
 ///
 /// # Returns
 ///
 /// A `String` containing the DKIM signature header prepended to the original email.
+///
+/// # Panics
+///
+/// Panics if the key cannot be parsed or the email cannot be signed. Use
+/// [`try_sign_email`] to handle these errors instead.
 pub fn sign_email(email: &str, domain: &str, selector: &str, private_key: &str) -> String {
+    match try_sign_email(email, domain, selector, private_key) {
+        Ok(signed_email) => signed_email,
+        Err(err) => panic!("Failed to sign email: {err}"),
+    }
+}
+
+/// Signs an email with an RSA-SHA256 DKIM signature, like [`sign_email`], but
+/// returns an error instead of panicking when the key or message is invalid.
+pub fn try_sign_email(
+    email: &str,
+    domain: &str,
+    selector: &str,
+    private_key: &str,
+) -> Result<String> {
     // Sign an e-mail message using RSA-SHA256
-    let pk_rsa = RsaKey::<Sha256>::from_pkcs1_pem(private_key).unwrap();
+    let pk_rsa = RsaKey::<Sha256>::from_pem(private_key)?;
     let signature_rsa = DkimSigner::from_key(pk_rsa)
         .domain(domain)
         .selector(selector)
-        .headers(["From", "To", "Subject"])
-        .sign(email.as_bytes())
-        .unwrap();
+        .recommended_headers()
+        .sign(email.as_bytes())?;
 
-    format!("{}{}", signature_rsa.to_header(), email)
+    Ok(format!("{}{}", signature_rsa.try_to_header()?, email))
 }
 
 /// A minimal error type for the library.
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub enum Error {
+    MissingTag(String),
+    InvalidTag(String),
+    DuplicateTag(String),
     NoHeadersFound,
+    MissingFrom,
+    MalformedMessage,
+    NonUtf8HeaderName,
+    NonUtf8Header,
+    InvalidKey(String),
+    EncryptedKey,
+    KeyDecryption(String),
+    UnsupportedKeyFormat(String),
     CryptoError(String),
     Base64,
+    UnsupportedVersion,
+    UnsupportedAlgorithm,
+    UnsupportedCanonicalization,
+    UnsupportedKeyType,
+    FailedBodyHashMatch,
+    FailedVerification,
+    FailedAuidMatch,
+    SignatureLength,
+    SignatureExpired,
+    RevokedPublicKey,
+    IncompatibleAlgorithms,
+    DnsError(String),
+    DnsRecordNotFound,
+    Io(String),
+    InvalidSigningTable(usize),
+    InvalidArcChain,
+    ArcChainTooLong,
+    InvalidSpfRecord(String),
+    MultipleSpfRecords,
+    TooManyDnsLookups,
+    TooManyVoidLookups,
+    HeadersTooLarge,
 }
 
 pub type Result<T> = std::result::Result<T, Error>;
@@ -49,9 +119,57 @@ impl std::error::Error for Error {}
 impl std::fmt::Display for Error {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
+            Error::MissingTag(tag) => write!(f, "Missing required tag '{tag}'"),
+            Error::InvalidTag(tag) => write!(f, "Invalid value for tag '{tag}'"),
+            Error::DuplicateTag(tag) => write!(f, "Tag '{tag}' appears more than once"),
             Error::NoHeadersFound => write!(f, "No headers found to sign"),
+            Error::MissingFrom => write!(f, "Message has no From header"),
+            Error::MalformedMessage => write!(f, "Malformed message"),
+            Error::NonUtf8HeaderName => write!(f, "Header name is not valid UTF-8"),
+            Error::NonUtf8Header => write!(f, "Header is not valid UTF-8"),
+            Error::InvalidKey(err) => write!(f, "Invalid key: {err}"),
+            Error::EncryptedKey => write!(f, "Private key is encrypted and needs a password"),
+            Error::KeyDecryption(err) => write!(f, "Failed to decrypt private key: {err}"),
+            Error::UnsupportedKeyFormat(label) => {
+                write!(f, "Unsupported key format '{label}'")
+            }
             Error::CryptoError(err) => write!(f, "Cryptography error: {err}"),
             Error::Base64 => write!(f, "Base64 encoding error."),
+            Error::UnsupportedVersion => write!(f, "Unsupported version"),
+            Error::UnsupportedAlgorithm => write!(f, "Unsupported algorithm"),
+            Error::UnsupportedCanonicalization => write!(f, "Unsupported canonicalization"),
+            Error::UnsupportedKeyType => write!(f, "Unsupported key type"),
+            Error::FailedBodyHashMatch => {
+                write!(f, "Calculated body hash does not match signature hash")
+            }
+            Error::FailedVerification => write!(f, "Signature verification failed"),
+            Error::FailedAuidMatch => write!(f, "AUID does not match domain name"),
+            Error::SignatureExpired => write!(f, "Signature has expired"),
+            Error::SignatureLength => {
+                write!(f, "Body length tag exceeds the length of the message body")
+            }
+            Error::RevokedPublicKey => write!(f, "Public key for this signature has been revoked"),
+            Error::IncompatibleAlgorithms => {
+                write!(
+                    f,
+                    "Incompatible algorithms used in signature and DKIM DNS record"
+                )
+            }
+            Error::DnsError(err) => write!(f, "DNS resolution error: {err}"),
+            Error::DnsRecordNotFound => write!(f, "DNS record not found"),
+            Error::Io(err) => write!(f, "I/O error: {err}"),
+            Error::InvalidSigningTable(line) => {
+                write!(f, "Invalid signing table entry on line {line}")
+            }
+            Error::InvalidArcChain => write!(f, "ARC chain is broken"),
+            Error::ArcChainTooLong => write!(f, "ARC chain has more than 50 sets"),
+            Error::InvalidSpfRecord(term) => write!(f, "Invalid SPF record term '{term}'"),
+            Error::MultipleSpfRecords => write!(f, "Domain publishes more than one SPF record"),
+            Error::TooManyDnsLookups => write!(f, "SPF evaluation exceeded 10 DNS lookups"),
+            Error::TooManyVoidLookups => {
+                write!(f, "SPF evaluation exceeded 2 lookups returning no records")
+            }
+            Error::HeadersTooLarge => write!(f, "Header section exceeds the maximum size"),
         }
     }
 }
@@ -66,6 +184,26 @@ impl From<rsa::errors::Error> for Error {
 // Convert from rsa::pkcs1::Error to our custom Error type.
 impl From<rsa::pkcs1::Error> for Error {
     fn from(err: rsa::pkcs1::Error) -> Self {
-        Error::CryptoError(err.to_string())
+        Error::InvalidKey(err.to_string())
+    }
+}
+
+// Convert from pkcs8::Error to our custom Error type.
+impl From<pkcs8::Error> for Error {
+    fn from(err: pkcs8::Error) -> Self {
+        match err {
+            pkcs8::Error::PublicKey(pkcs8::spki::Error::OidUnknown { .. }) => {
+                Error::UnsupportedKeyType
+            }
+            pkcs8::Error::EncryptedPrivateKey(err) => Error::KeyDecryption(err.to_string()),
+            err => Error::InvalidKey(err.to_string()),
+        }
+    }
+}
+
+// Convert from std::io::Error to our custom Error type.
+impl From<std::io::Error> for Error {
+    fn from(err: std::io::Error) -> Self {
+        Error::Io(err.to_string())
     }
 }
@@ -0,0 +1,118 @@
+use super::{MacroPart, MacroString};
+use std::{fmt::Write, net::IpAddr};
+
+/// The values substituted for macros (RFC 7208, section 7.2).
+pub(crate) struct MacroVariables<'a> {
+    pub(crate) sender: &'a str,
+    pub(crate) local_part: &'a str,
+    pub(crate) sender_domain: &'a str,
+    pub(crate) domain: &'a str,
+    pub(crate) ip: IpAddr,
+    /// The validated domain name of `ip`, only looked up when `%{p}` is used.
+    pub(crate) validated_domain: &'a str,
+    pub(crate) helo: &'a str,
+    pub(crate) receiver: &'a str,
+    pub(crate) now: u64,
+}
+
+impl MacroString {
+    /// Whether the macro-string uses the macro `letter`.
+    pub(crate) fn uses(&self, letter: char) -> bool {
+        self.parts
+            .iter()
+            .any(|part| matches!(part, MacroPart::Macro { letter: l, .. } if *l == letter))
+    }
+
+    pub(crate) fn expand(&self, vars: &MacroVariables<'_>) -> String {
+        let mut result = String::new();
+
+        for part in &self.parts {
+            let (letter, digits, reverse, delimiters, escape) = match part {
+                MacroPart::Literal(literal) => {
+                    result.push_str(literal);
+                    continue;
+                }
+                MacroPart::Macro {
+                    letter,
+                    digits,
+                    reverse,
+                    delimiters,
+                    escape,
+                } => (*letter, *digits, *reverse, delimiters, *escape),
+            };
+
+            let value = match letter {
+                's' => vars.sender.to_string(),
+                'l' => vars.local_part.to_string(),
+                'o' => vars.sender_domain.to_string(),
+                'd' => vars.domain.to_string(),
+                'i' => dotted_ip(vars.ip),
+                'p' => vars.validated_domain.to_string(),
+                'v' => match vars.ip {
+                    IpAddr::V4(_) => "in-addr".to_string(),
+                    IpAddr::V6(_) => "ip6".to_string(),
+                },
+                'h' => vars.helo.to_string(),
+                'c' => vars.ip.to_string(),
+                'r' => vars.receiver.to_string(),
+                't' => vars.now.to_string(),
+                _ => String::new(),
+            };
+
+            // Split on the delimiters, then reverse and truncate the parts
+            let delimiters = if delimiters.is_empty() {
+                "."
+            } else {
+                delimiters
+            };
+            let mut parts = value
+                .split(|ch| delimiters.contains(ch))
+                .collect::<Vec<_>>();
+            if reverse {
+                parts.reverse();
+            }
+            if let Some(digits) = digits {
+                parts.drain(..parts.len().saturating_sub(digits));
+            }
+            let value = parts.join(".");
+
+            if escape {
+                url_escape(&value, &mut result);
+            } else {
+                result.push_str(&value);
+            }
+        }
+
+        result
+    }
+}
+
+/// Formats an IPv4 address in dotted-quad form and an IPv6 address as dot
+/// separated nibbles.
+fn dotted_ip(ip: IpAddr) -> String {
+    match ip {
+        IpAddr::V4(ip) => ip.to_string(),
+        IpAddr::V6(ip) => {
+            let mut result = String::with_capacity(63);
+            for byte in ip.octets() {
+                for nibble in [byte >> 4, byte & 0x0f] {
+                    if !result.is_empty() {
+                        result.push('.');
+                    }
+                    let _ = write!(result, "{nibble:x}");
+                }
+            }
+            result
+        }
+    }
+}
+
+fn url_escape(value: &str, result: &mut String) {
+    for byte in value.bytes() {
+        if byte.is_ascii_alphanumeric() || matches!(byte, b'-' | b'.' | b'_' | b'~') {
+            result.push(byte as char);
+        } else {
+            let _ = write!(result, "%{byte:02X}");
+        }
+    }
+}
@@ -0,0 +1,125 @@
+pub mod macros;
+pub mod parse;
+pub mod verify;
+
+use crate::Error;
+use std::net::{Ipv4Addr, Ipv6Addr};
+
+/// The maximum number of mechanisms and modifiers causing DNS lookups that
+/// are evaluated for a message (RFC 7208, section 4.6.4).
+pub const MAX_DNS_LOOKUPS: u32 = 10;
+
+/// The maximum number of DNS lookups returning no records (RFC 7208,
+/// section 4.6.4).
+pub const MAX_VOID_LOOKUPS: u32 = 2;
+
+/// An SPF record (RFC 7208, section 4.5), such as `v=spf1 mx -all`.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct SpfRecord {
+    pub directives: Vec<Directive>,
+    /// The `redirect=` modifier.
+    pub redirect: Option<MacroString>,
+    /// The `exp=` modifier, naming the TXT record explaining failures.
+    pub exp: Option<MacroString>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Directive {
+    pub qualifier: Qualifier,
+    pub mechanism: Mechanism,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Qualifier {
+    #[default]
+    Pass,
+    Fail,
+    SoftFail,
+    Neutral,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Mechanism {
+    All,
+    Include(MacroString),
+    A {
+        domain: Option<MacroString>,
+        ip4_cidr: u8,
+        ip6_cidr: u8,
+    },
+    Mx {
+        domain: Option<MacroString>,
+        ip4_cidr: u8,
+        ip6_cidr: u8,
+    },
+    Ptr(Option<MacroString>),
+    Ip4 {
+        addr: Ipv4Addr,
+        cidr: u8,
+    },
+    Ip6 {
+        addr: Ipv6Addr,
+        cidr: u8,
+    },
+    Exists(MacroString),
+}
+
+/// A domain specification or explanation that may contain macros, such as
+/// `%{ir}.%{v}._spf.%{d}` (RFC 7208, section 7).
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct MacroString {
+    pub(crate) parts: Vec<MacroPart>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum MacroPart {
+    Literal(String),
+    Macro {
+        /// The lowercase macro letter.
+        letter: char,
+        /// Keep only this many of the rightmost parts.
+        digits: Option<usize>,
+        reverse: bool,
+        delimiters: String,
+        /// URL-escape the expansion, requested by an uppercase letter.
+        escape: bool,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SpfResult {
+    None,
+    Neutral,
+    Pass,
+    Fail,
+    SoftFail,
+    TempError(Error),
+    PermError(Error),
+}
+
+/// The identity whose authorization is checked.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SpfIdentity {
+    /// The `MAIL FROM` address, or `postmaster@` the HELO domain for bounces.
+    MailFrom,
+    Helo,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SpfOutput {
+    pub result: SpfResult,
+    pub identity: SpfIdentity,
+    /// The domain whose SPF record was evaluated.
+    pub domain: String,
+    /// The sender address used for macro expansion.
+    pub sender: String,
+    /// The expanded `exp=` explanation of a failure, if the domain published one.
+    pub explanation: Option<String>,
+}
+
+/// Checks whether a client IP address may send mail for a domain (RFC 7208).
+#[derive(Debug, Clone, Default)]
+pub struct SpfVerifier<R> {
+    pub(crate) resolver: R,
+    pub(crate) receiver: String,
+}
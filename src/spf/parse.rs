@@ -0,0 +1,478 @@
+use super::{Directive, MacroPart, MacroString, Mechanism, Qualifier, SpfRecord};
+use crate::Error;
+use std::str::FromStr;
+
+impl SpfRecord {
+    /// Parses an SPF record starting with `v=spf1`. Unknown modifiers are
+    /// ignored, while unknown mechanisms and repeated `redirect=` or `exp=`
+    /// modifiers are errors.
+    pub fn parse(record: &str) -> crate::Result<Self> {
+        let mut terms = record.split_ascii_whitespace();
+        if !terms
+            .next()
+            .is_some_and(|version| version.eq_ignore_ascii_case("v=spf1"))
+        {
+            return Err(Error::UnsupportedVersion);
+        }
+
+        let mut spf = SpfRecord::default();
+        for term in terms {
+            if let Some((name, value)) = split_modifier(term) {
+                let modifier = if name.eq_ignore_ascii_case("redirect") {
+                    &mut spf.redirect
+                } else if name.eq_ignore_ascii_case("exp") {
+                    &mut spf.exp
+                } else {
+                    // Unknown modifiers are ignored, but have to be well formed
+                    MacroString::parse(value, false)?;
+                    continue;
+                };
+                if modifier.replace(parse_domain_spec(value)?).is_some() {
+                    return Err(Error::InvalidSpfRecord(term.to_string()));
+                }
+            } else {
+                spf.directives.push(Directive::parse(term)?);
+            }
+        }
+
+        Ok(spf)
+    }
+
+    /// Whether a TXT record is an SPF record, selected by its version.
+    pub(crate) fn is_spf_record(record: &[u8]) -> bool {
+        record
+            .get(..6)
+            .is_some_and(|version| version.eq_ignore_ascii_case(b"v=spf1"))
+            && record.get(6).is_none_or(|&ch| ch == b' ')
+    }
+}
+
+impl FromStr for SpfRecord {
+    type Err = Error;
+
+    fn from_str(s: &str) -> crate::Result<Self> {
+        SpfRecord::parse(s)
+    }
+}
+
+impl Directive {
+    fn parse(term: &str) -> crate::Result<Self> {
+        let invalid = || Error::InvalidSpfRecord(term.to_string());
+        let (qualifier, mechanism) = match term.as_bytes().first() {
+            Some(b'+') => (Qualifier::Pass, &term[1..]),
+            Some(b'-') => (Qualifier::Fail, &term[1..]),
+            Some(b'~') => (Qualifier::SoftFail, &term[1..]),
+            Some(b'?') => (Qualifier::Neutral, &term[1..]),
+            _ => (Qualifier::Pass, term),
+        };
+        let (name, argument) = match mechanism.find([':', '/']) {
+            Some(pos) => mechanism.split_at(pos),
+            None => (mechanism, ""),
+        };
+        let domain_spec = argument.strip_prefix(':');
+
+        let mechanism = match name.to_ascii_lowercase().as_str() {
+            "all" if argument.is_empty() => Mechanism::All,
+            "include" => Mechanism::Include(parse_domain_spec(domain_spec.ok_or_else(invalid)?)?),
+            "exists" => Mechanism::Exists(parse_domain_spec(domain_spec.ok_or_else(invalid)?)?),
+            "ptr" => Mechanism::Ptr(domain_spec.map(parse_domain_spec).transpose()?),
+            "a" | "mx" => {
+                // The domain is followed by optional IPv4 and IPv6 prefix lengths
+                let (domain, cidr) = match domain_spec {
+                    Some(spec) => match spec.find('/') {
+                        Some(pos) => (Some(&spec[..pos]), &spec[pos..]),
+                        None => (Some(spec), ""),
+                    },
+                    None => (None, argument),
+                };
+                let (ip4_cidr, ip6_cidr) = match cidr.split_once("//") {
+                    Some((ip4, ip6)) => (ip4, Some(ip6)),
+                    None => (cidr, None),
+                };
+                let ip4_cidr = match ip4_cidr.strip_prefix('/') {
+                    Some(cidr) => parse_cidr(cidr, 32).ok_or_else(invalid)?,
+                    None if ip4_cidr.is_empty() => 32,
+                    None => return Err(invalid()),
+                };
+                let ip6_cidr = match ip6_cidr {
+                    Some(cidr) => parse_cidr(cidr, 128).ok_or_else(invalid)?,
+                    None => 128,
+                };
+                let domain = domain.map(parse_domain_spec).transpose()?;
+                if name.eq_ignore_ascii_case("a") {
+                    Mechanism::A {
+                        domain,
+                        ip4_cidr,
+                        ip6_cidr,
+                    }
+                } else {
+                    Mechanism::Mx {
+                        domain,
+                        ip4_cidr,
+                        ip6_cidr,
+                    }
+                }
+            }
+            "ip4" => {
+                let (addr, cidr) = split_network(domain_spec.ok_or_else(invalid)?);
+                Mechanism::Ip4 {
+                    addr: addr.parse().map_err(|_| invalid())?,
+                    cidr: cidr
+                        .map_or(Some(32), |cidr| parse_cidr(cidr, 32))
+                        .ok_or_else(invalid)?,
+                }
+            }
+            "ip6" => {
+                let (addr, cidr) = split_network(domain_spec.ok_or_else(invalid)?);
+                Mechanism::Ip6 {
+                    addr: addr.parse().map_err(|_| invalid())?,
+                    cidr: cidr
+                        .map_or(Some(128), |cidr| parse_cidr(cidr, 128))
+                        .ok_or_else(invalid)?,
+                }
+            }
+            _ => return Err(invalid()),
+        };
+
+        Ok(Directive {
+            qualifier,
+            mechanism,
+        })
+    }
+}
+
+impl MacroString {
+    /// Parses a macro-string. Explanations may also use the `c`, `r` and `t`
+    /// macros, along with spaces.
+    pub(crate) fn parse(value: &str, explanation: bool) -> crate::Result<Self> {
+        let invalid = || Error::InvalidSpfRecord(value.to_string());
+        let mut parts = Vec::new();
+        let mut literal = String::new();
+        let mut chars = value.chars();
+
+        while let Some(ch) = chars.next() {
+            if ch != '%' {
+                literal.push(ch);
+                continue;
+            }
+            match chars.next() {
+                Some('%') => literal.push('%'),
+                Some('_') => literal.push(' '),
+                Some('-') => literal.push_str("%20"),
+                Some('{') => {
+                    let letter = chars.next().ok_or_else(invalid)?;
+                    let valid_letters = if explanation {
+                        "slodipvhcrt"
+                    } else {
+                        "slodipvh"
+                    };
+                    if !valid_letters.contains(letter.to_ascii_lowercase()) {
+                        return Err(invalid());
+                    }
+
+                    let mut digits = String::new();
+                    let mut reverse = false;
+                    let mut delimiters = String::new();
+                    loop {
+                        match chars.next().ok_or_else(invalid)? {
+                            '}' => break,
+                            ch @ '0'..='9' if !reverse && delimiters.is_empty() => digits.push(ch),
+                            'r' | 'R' if !reverse && delimiters.is_empty() => reverse = true,
+                            ch @ ('.' | '-' | '+' | ',' | '/' | '_' | '=') => delimiters.push(ch),
+                            _ => return Err(invalid()),
+                        }
+                    }
+                    let digits = match digits.as_str() {
+                        "" => None,
+                        digits => Some(digits.parse().ok().filter(|&n| n > 0).ok_or_else(invalid)?),
+                    };
+
+                    if !literal.is_empty() {
+                        parts.push(MacroPart::Literal(std::mem::take(&mut literal)));
+                    }
+                    parts.push(MacroPart::Macro {
+                        letter: letter.to_ascii_lowercase(),
+                        digits,
+                        reverse,
+                        delimiters,
+                        escape: letter.is_ascii_uppercase(),
+                    });
+                }
+                _ => return Err(invalid()),
+            }
+        }
+
+        if !literal.is_empty() {
+            parts.push(MacroPart::Literal(literal));
+        }
+        Ok(MacroString { parts })
+    }
+}
+
+/// Splits a `name=value` modifier from a term, returning `None` for mechanisms.
+fn split_modifier(term: &str) -> Option<(&str, &str)> {
+    let (name, value) = term.split_once('=')?;
+    (name.starts_with(|ch: char| ch.is_ascii_alphabetic())
+        && name
+            .chars()
+            .all(|ch| ch.is_ascii_alphanumeric() || matches!(ch, '-' | '_' | '.')))
+    .then_some((name, value))
+}
+
+fn parse_domain_spec(value: &str) -> crate::Result<MacroString> {
+    if value.is_empty() {
+        return Err(Error::InvalidSpfRecord(value.to_string()));
+    }
+    MacroString::parse(value, false)
+}
+
+fn split_network(value: &str) -> (&str, Option<&str>) {
+    match value.split_once('/') {
+        Some((addr, cidr)) => (addr, Some(cidr)),
+        None => (value, None),
+    }
+}
+
+fn parse_cidr(value: &str, max: u8) -> Option<u8> {
+    if value.is_empty() || (value.len() > 1 && value.starts_with('0')) {
+        return None;
+    }
+    value.parse().ok().filter(|&cidr| cidr <= max)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        spf::{Directive, MacroPart, MacroString, Mechanism, Qualifier},
+        Error, MemoryResolver, SpfRecord, SpfResult, SpfVerifier,
+    };
+
+    fn spec(value: &str) -> MacroString {
+        MacroString::parse(value, false).unwrap()
+    }
+
+    fn mechanisms(record: &str) -> Vec<Mechanism> {
+        record
+            .parse::<SpfRecord>()
+            .unwrap()
+            .directives
+            .into_iter()
+            .map(|directive| directive.mechanism)
+            .collect()
+    }
+
+    #[test]
+    fn parse_record() {
+        let record: SpfRecord =
+            "v=spf1 +a -mx ~ptr:example.org ?include:_spf.example.com exists:%{i}.example.com all"
+                .parse()
+                .unwrap();
+        assert_eq!(
+            record.directives,
+            [
+                Directive {
+                    qualifier: Qualifier::Pass,
+                    mechanism: Mechanism::A {
+                        domain: None,
+                        ip4_cidr: 32,
+                        ip6_cidr: 128
+                    },
+                },
+                Directive {
+                    qualifier: Qualifier::Fail,
+                    mechanism: Mechanism::Mx {
+                        domain: None,
+                        ip4_cidr: 32,
+                        ip6_cidr: 128
+                    },
+                },
+                Directive {
+                    qualifier: Qualifier::SoftFail,
+                    mechanism: Mechanism::Ptr(Some(spec("example.org"))),
+                },
+                Directive {
+                    qualifier: Qualifier::Neutral,
+                    mechanism: Mechanism::Include(spec("_spf.example.com")),
+                },
+                Directive {
+                    qualifier: Qualifier::Pass,
+                    mechanism: Mechanism::Exists(spec("%{i}.example.com")),
+                },
+                Directive {
+                    qualifier: Qualifier::Pass,
+                    mechanism: Mechanism::All,
+                },
+            ]
+        );
+        assert_eq!(record.redirect, None);
+        assert_eq!(record.exp, None);
+        assert_eq!(
+            spec("%{ir}.x").parts,
+            [
+                MacroPart::Macro {
+                    letter: 'i',
+                    digits: None,
+                    reverse: true,
+                    delimiters: String::new(),
+                    escape: false,
+                },
+                MacroPart::Literal(".x".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn parse_version() {
+        for record in ["v=spf1", "V=SPF1 -all", "v=Spf1  mx   -all "] {
+            assert!(record.parse::<SpfRecord>().is_ok(), "{record}");
+        }
+        for record in [
+            "",
+            "v=spf10 -all",
+            "spf1 -all",
+            "v=spf2 -all",
+            " -all v=spf1",
+        ] {
+            assert_eq!(
+                record.parse::<SpfRecord>(),
+                Err(Error::UnsupportedVersion),
+                "{record}"
+            );
+        }
+        assert!(SpfRecord::is_spf_record(b"v=spf1"));
+        assert!(SpfRecord::is_spf_record(b"V=SPF1 -all"));
+        assert!(!SpfRecord::is_spf_record(b"v=spf10 -all"));
+        assert!(!SpfRecord::is_spf_record(b"v=spf1\t-all"));
+    }
+
+    #[test]
+    fn parse_cidr() {
+        assert_eq!(
+            mechanisms(
+                "v=spf1 ip4:0.0.0.0/0 ip4:192.0.2.1 ip4:192.0.2.0/32 ip6:::/0 ip6:2001:db8::/128"
+            ),
+            [
+                Mechanism::Ip4 {
+                    addr: [0, 0, 0, 0].into(),
+                    cidr: 0
+                },
+                Mechanism::Ip4 {
+                    addr: [192, 0, 2, 1].into(),
+                    cidr: 32
+                },
+                Mechanism::Ip4 {
+                    addr: [192, 0, 2, 0].into(),
+                    cidr: 32
+                },
+                Mechanism::Ip6 {
+                    addr: "::".parse().unwrap(),
+                    cidr: 0
+                },
+                Mechanism::Ip6 {
+                    addr: "2001:db8::".parse().unwrap(),
+                    cidr: 128
+                },
+            ]
+        );
+        assert_eq!(
+            mechanisms("v=spf1 a/24//64 a/24 a//64 mx:example.com/0//0 a:example.com//48"),
+            [
+                Mechanism::A {
+                    domain: None,
+                    ip4_cidr: 24,
+                    ip6_cidr: 64
+                },
+                Mechanism::A {
+                    domain: None,
+                    ip4_cidr: 24,
+                    ip6_cidr: 128
+                },
+                Mechanism::A {
+                    domain: None,
+                    ip4_cidr: 32,
+                    ip6_cidr: 64
+                },
+                Mechanism::Mx {
+                    domain: Some(spec("example.com")),
+                    ip4_cidr: 0,
+                    ip6_cidr: 0
+                },
+                Mechanism::A {
+                    domain: Some(spec("example.com")),
+                    ip4_cidr: 32,
+                    ip6_cidr: 48
+                },
+            ]
+        );
+
+        for term in [
+            "ip4:192.0.2.0/33",
+            "ip4:192.0.2.0/",
+            "ip4:192.0.2.0/024",
+            "ip4:192.0.2",
+            "ip4:2001:db8::1",
+            "ip6:2001:db8::/129",
+            "ip6:192.0.2.1",
+            "a/33",
+            "a//129",
+            "mx:example.com/",
+            "a24",
+        ] {
+            let record = format!("v=spf1 {term} -all");
+            assert_eq!(
+                record.parse::<SpfRecord>(),
+                Err(Error::InvalidSpfRecord(term.to_string())),
+                "{term}"
+            );
+        }
+        // The domain-spec following `:` cannot be empty
+        assert!("v=spf1 a:/24//64".parse::<SpfRecord>().is_err());
+    }
+
+    #[test]
+    fn parse_modifiers() {
+        let record: SpfRecord =
+            "v=spf1 mx redirect=_spf.example.com exp=explain.%{d} unknown=%{d}.x -all"
+                .parse()
+                .unwrap();
+        assert_eq!(record.directives.len(), 2);
+        assert_eq!(record.redirect, Some(spec("_spf.example.com")));
+        assert_eq!(record.exp, Some(spec("explain.%{d}")));
+
+        for (record, term) in [
+            (
+                "v=spf1 redirect=a.example REDIRECT=b.example",
+                "REDIRECT=b.example",
+            ),
+            ("v=spf1 exp=a.example -all exp=b.example", "exp=b.example"),
+            ("v=spf1 redirect=", ""),
+            ("v=spf1 unknown=%{x}", "%{x}"),
+            ("v=spf1 foo -all", "foo"),
+            ("v=spf1 include -all", "include"),
+            ("v=spf1 all:example.com", "all:example.com"),
+        ] {
+            assert_eq!(
+                record.parse::<SpfRecord>(),
+                Err(Error::InvalidSpfRecord(term.to_string())),
+                "{record}"
+            );
+        }
+
+        // Records that fail to parse are permanent errors
+        let mut resolver = MemoryResolver::new();
+        resolver.txt_add(
+            "example.com",
+            "v=spf1 redirect=a.example redirect=b.example",
+        );
+        let output = SpfVerifier::new(resolver).check_host(
+            "192.0.2.1".parse().unwrap(),
+            "example.com",
+            "user@example.com",
+            "mx.example.com",
+        );
+        assert_eq!(
+            output.result,
+            SpfResult::PermError(Error::InvalidSpfRecord("redirect=b.example".to_string()))
+        );
+    }
+}
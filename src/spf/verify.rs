@@ -0,0 +1,702 @@
+use super::{
+    macros::MacroVariables, MacroString, Mechanism, Qualifier, SpfIdentity, SpfOutput, SpfRecord,
+    SpfResult, SpfVerifier, MAX_DNS_LOOKUPS, MAX_VOID_LOOKUPS,
+};
+use crate::{
+    common::{
+        clock::{Clock, SystemClock},
+        resolver::Resolver,
+    },
+    Error,
+};
+use std::net::IpAddr;
+
+impl<R: Resolver> SpfVerifier<R> {
+    pub fn new(resolver: R) -> Self {
+        SpfVerifier {
+            resolver,
+            receiver: "unknown".to_string(),
+        }
+    }
+
+    /// Sets the host name of the receiving MTA, used by the `%{r}` macro of
+    /// explanations.
+    pub fn receiver(mut self, receiver: impl Into<String>) -> Self {
+        self.receiver = receiver.into();
+        self
+    }
+
+    /// Checks the HELO identity of a client (RFC 7208, section 2.3).
+    pub fn verify_helo(&self, ip: IpAddr, helo: &str) -> SpfOutput {
+        let sender = format!("postmaster@{helo}");
+        let mut output = self.check_host(ip, helo, &sender, helo);
+        output.identity = SpfIdentity::Helo;
+        output
+    }
+
+    /// Checks the `MAIL FROM` identity of a message (RFC 7208, section 2.4).
+    /// Bounces, which have an empty `MAIL FROM`, are checked against
+    /// `postmaster@` the HELO domain.
+    pub fn verify_mail_from(&self, ip: IpAddr, helo: &str, mail_from: &str) -> SpfOutput {
+        let mail_from = mail_from
+            .trim()
+            .trim_start_matches('<')
+            .trim_end_matches('>');
+        let sender = match mail_from.rsplit_once('@') {
+            _ if mail_from.is_empty() => format!("postmaster@{helo}"),
+            Some(("", domain)) => format!("postmaster@{domain}"),
+            Some(_) => mail_from.to_string(),
+            None => format!("postmaster@{mail_from}"),
+        };
+        let domain = sender.rsplit_once('@').map_or("", |(_, domain)| domain);
+        self.check_host(ip, domain, &sender, helo)
+    }
+
+    /// Evaluates the SPF record of `domain` for a message sent by `sender`
+    /// from `ip`, which is the `check_host()` function of RFC 7208, section 4.
+    pub fn check_host(&self, ip: IpAddr, domain: &str, sender: &str, helo: &str) -> SpfOutput {
+        self.check_host_with_clock(ip, domain, sender, helo, &SystemClock)
+    }
+
+    /// Evaluates the SPF record of `domain`, reading the time used by the
+    /// `%{t}` macro from `clock`.
+    pub fn check_host_with_clock(
+        &self,
+        ip: IpAddr,
+        domain: &str,
+        sender: &str,
+        helo: &str,
+        clock: &impl Clock,
+    ) -> SpfOutput {
+        let (local_part, sender_domain) = sender.rsplit_once('@').unwrap_or(("postmaster", sender));
+        let mut evaluation = Evaluation {
+            resolver: &self.resolver,
+            ip: ip.to_canonical(),
+            sender,
+            local_part: if local_part.is_empty() {
+                "postmaster"
+            } else {
+                local_part
+            },
+            sender_domain,
+            helo,
+            receiver: &self.receiver,
+            now: clock.now(),
+            lookups: 0,
+            void_lookups: 0,
+        };
+        let domain = domain.trim_end_matches('.');
+        let (result, explanation) = evaluation.check_host(domain, true);
+
+        SpfOutput {
+            result,
+            identity: SpfIdentity::MailFrom,
+            domain: domain.to_string(),
+            sender: sender.to_string(),
+            explanation,
+        }
+    }
+}
+
+/// The state of a `check_host()` evaluation, shared by the records reached
+/// through `include` and `redirect`, as the DNS lookup limits apply to all of them.
+struct Evaluation<'a, R> {
+    resolver: &'a R,
+    ip: IpAddr,
+    sender: &'a str,
+    local_part: &'a str,
+    sender_domain: &'a str,
+    helo: &'a str,
+    receiver: &'a str,
+    now: u64,
+    lookups: u32,
+    void_lookups: u32,
+}
+
+impl<R: Resolver> Evaluation<'_, R> {
+    /// Evaluates the record of `domain`. The explanation of a `Fail` is only
+    /// looked up when `explain` is set, as those of included records are not used.
+    fn check_host(&mut self, domain: &str, explain: bool) -> (SpfResult, Option<String>) {
+        // Only multi-label domain names can publish a record
+        if !domain.contains('.') || !is_valid_domain(domain) {
+            return (SpfResult::None, None);
+        }
+        let record = match self.fetch_record(domain) {
+            Ok(record) => record,
+            Err(result) => return (result, None),
+        };
+
+        for directive in &record.directives {
+            match self.matches(&directive.mechanism, domain) {
+                Ok(true) => {
+                    return match directive.qualifier {
+                        Qualifier::Pass => (SpfResult::Pass, None),
+                        Qualifier::Fail if explain => {
+                            (SpfResult::Fail, self.explanation(&record, domain))
+                        }
+                        Qualifier::Fail => (SpfResult::Fail, None),
+                        Qualifier::SoftFail => (SpfResult::SoftFail, None),
+                        Qualifier::Neutral => (SpfResult::Neutral, None),
+                    };
+                }
+                Ok(false) => (),
+                Err(result) => return (result, None),
+            }
+        }
+
+        // The redirect modifier only applies when no mechanism matched
+        match &record.redirect {
+            Some(redirect) => {
+                let target = match self
+                    .count_lookup()
+                    .and_then(|_| self.target_domain(Some(redirect), domain))
+                {
+                    Ok(target) => target,
+                    Err(result) => return (result, None),
+                };
+                match self.check_host(&target, explain) {
+                    (SpfResult::None, _) => (SpfResult::PermError(Error::DnsRecordNotFound), None),
+                    result => result,
+                }
+            }
+            None => (SpfResult::Neutral, None),
+        }
+    }
+
+    fn fetch_record(&mut self, domain: &str) -> Result<SpfRecord, SpfResult> {
+        let records = match self.resolver.txt_lookup(domain) {
+            Ok(records) => records,
+            Err(Error::DnsRecordNotFound) => return Err(SpfResult::None),
+            Err(err) => return Err(SpfResult::TempError(err)),
+        };
+        let mut records = records
+            .iter()
+            .filter(|record| SpfRecord::is_spf_record(record));
+
+        match (records.next(), records.next()) {
+            (Some(record), None) => std::str::from_utf8(record)
+                .map_err(|_| Error::InvalidSpfRecord(String::from_utf8_lossy(record).into_owned()))
+                .and_then(SpfRecord::parse)
+                .map_err(SpfResult::PermError),
+            (Some(_), Some(_)) => Err(SpfResult::PermError(Error::MultipleSpfRecords)),
+            (None, _) => Err(SpfResult::None),
+        }
+    }
+
+    fn matches(&mut self, mechanism: &Mechanism, domain: &str) -> Result<bool, SpfResult> {
+        match mechanism {
+            Mechanism::All => Ok(true),
+            Mechanism::Include(spec) => {
+                self.count_lookup()?;
+                let target = self.target_domain(Some(spec), domain)?;
+                match self.check_host(&target, false).0 {
+                    SpfResult::Pass => Ok(true),
+                    SpfResult::Fail | SpfResult::SoftFail | SpfResult::Neutral => Ok(false),
+                    SpfResult::None => Err(SpfResult::PermError(Error::DnsRecordNotFound)),
+                    result => Err(result),
+                }
+            }
+            Mechanism::A {
+                domain: spec,
+                ip4_cidr,
+                ip6_cidr,
+            } => {
+                self.count_lookup()?;
+                let target = self.target_domain(spec.as_ref(), domain)?;
+                let addresses = self.addresses(&target, true)?;
+                Ok(addresses
+                    .into_iter()
+                    .any(|addr| in_network(self.ip, addr, *ip4_cidr, *ip6_cidr)))
+            }
+            Mechanism::Mx {
+                domain: spec,
+                ip4_cidr,
+                ip6_cidr,
+            } => {
+                self.count_lookup()?;
+                let target = self.target_domain(spec.as_ref(), domain)?;
+                let exchanges = self.void_check(self.resolver.mx_lookup(&target))?;
+                if exchanges.len() > MAX_DNS_LOOKUPS as usize {
+                    return Err(SpfResult::PermError(Error::TooManyDnsLookups));
+                }
+                for exchange in exchanges {
+                    if self
+                        .addresses(&exchange, false)?
+                        .into_iter()
+                        .any(|addr| in_network(self.ip, addr, *ip4_cidr, *ip6_cidr))
+                    {
+                        return Ok(true);
+                    }
+                }
+                Ok(false)
+            }
+            Mechanism::Ptr(spec) => {
+                self.count_lookup()?;
+                let target = self.target_domain(spec.as_ref(), domain)?;
+                Ok(self
+                    .validated_domains()
+                    .iter()
+                    .any(|name| is_subdomain_of(name, &target)))
+            }
+            Mechanism::Ip4 { addr, cidr } => Ok(in_network(self.ip, IpAddr::V4(*addr), *cidr, 0)),
+            Mechanism::Ip6 { addr, cidr } => Ok(in_network(self.ip, IpAddr::V6(*addr), 0, *cidr)),
+            Mechanism::Exists(spec) => {
+                self.count_lookup()?;
+                let target = self.target_domain(Some(spec), domain)?;
+                // Always an A lookup, whatever the version of the client address
+                Ok(!self
+                    .void_check(self.resolver.ipv4_lookup(&target))?
+                    .is_empty())
+            }
+        }
+    }
+
+    /// Expands the explanation published at the `exp=` domain of a record.
+    /// Any error results in no explanation.
+    fn explanation(&mut self, record: &SpfRecord, domain: &str) -> Option<String> {
+        let target = self
+            .target_domain(Some(record.exp.as_ref()?), domain)
+            .ok()?;
+        let records = self.resolver.txt_lookup(&target).ok()?;
+        let [explanation] = records.as_slice() else {
+            return None;
+        };
+        let explanation = MacroString::parse(std::str::from_utf8(explanation).ok()?, true).ok()?;
+        Some(self.expand(&explanation, domain))
+    }
+
+    /// Expands a domain-spec, defaulting to the current domain, and shortens
+    /// it to at most 253 characters by removing labels from the left.
+    fn target_domain(
+        &mut self,
+        spec: Option<&MacroString>,
+        domain: &str,
+    ) -> Result<String, SpfResult> {
+        let Some(spec) = spec else {
+            return Ok(domain.to_string());
+        };
+        let mut target = self.expand(spec, domain);
+        while target.len() > 253 {
+            match target.split_once('.') {
+                Some((_, rest)) => target = rest.to_string(),
+                None => break,
+            }
+        }
+        let target = target.trim_end_matches('.').to_string();
+        if is_valid_domain(&target) {
+            Ok(target)
+        } else {
+            Err(SpfResult::PermError(Error::InvalidSpfRecord(target)))
+        }
+    }
+
+    fn expand(&mut self, macro_string: &MacroString, domain: &str) -> String {
+        let validated_domain = if macro_string.uses('p') {
+            let names = self.validated_domains();
+            names
+                .iter()
+                .find(|name| is_subdomain_of(name, domain))
+                .or(names.first())
+                .cloned()
+                .unwrap_or_else(|| "unknown".to_string())
+        } else {
+            String::new()
+        };
+
+        macro_string.expand(&MacroVariables {
+            sender: self.sender,
+            local_part: self.local_part,
+            sender_domain: self.sender_domain,
+            domain,
+            ip: self.ip,
+            validated_domain: &validated_domain,
+            helo: self.helo,
+            receiver: self.receiver,
+            now: self.now,
+        })
+    }
+
+    /// Returns the host names of the client whose addresses include the client
+    /// address, checking at most 10 of them (RFC 7208, section 5.5).
+    fn validated_domains(&self) -> Vec<String> {
+        let names = self.resolver.ptr_lookup(self.ip).unwrap_or_default();
+        names
+            .into_iter()
+            .take(MAX_DNS_LOOKUPS as usize)
+            .map(|name| name.trim_end_matches('.').to_string())
+            .filter(|name| {
+                self.lookup_addresses(name)
+                    .is_ok_and(|addresses| addresses.contains(&self.ip))
+            })
+            .collect()
+    }
+
+    /// Looks up the addresses of `name` matching the version of the client
+    /// address. Only the lookups made directly by a mechanism count as void.
+    fn addresses(&mut self, name: &str, count_void: bool) -> Result<Vec<IpAddr>, SpfResult> {
+        let result = self.lookup_addresses(name);
+        if count_void {
+            self.void_check(result)
+        } else {
+            match result {
+                Ok(addresses) => Ok(addresses),
+                Err(Error::DnsRecordNotFound) => Ok(Vec::new()),
+                Err(err) => Err(SpfResult::TempError(err)),
+            }
+        }
+    }
+
+    fn lookup_addresses(&self, name: &str) -> crate::Result<Vec<IpAddr>> {
+        match self.ip {
+            IpAddr::V4(_) => self
+                .resolver
+                .ipv4_lookup(name)
+                .map(|addresses| addresses.into_iter().map(IpAddr::V4).collect()),
+            IpAddr::V6(_) => self
+                .resolver
+                .ipv6_lookup(name)
+                .map(|addresses| addresses.into_iter().map(IpAddr::V6).collect()),
+        }
+    }
+
+    fn count_lookup(&mut self) -> Result<(), SpfResult> {
+        self.lookups += 1;
+        if self.lookups > MAX_DNS_LOOKUPS {
+            Err(SpfResult::PermError(Error::TooManyDnsLookups))
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Counts lookups that returned no records, failing once there are too many.
+    fn void_check<T>(&mut self, result: crate::Result<Vec<T>>) -> Result<Vec<T>, SpfResult> {
+        match result {
+            Ok(records) if !records.is_empty() => Ok(records),
+            Ok(_) | Err(Error::DnsRecordNotFound) => {
+                self.void_lookups += 1;
+                if self.void_lookups > MAX_VOID_LOOKUPS {
+                    Err(SpfResult::PermError(Error::TooManyVoidLookups))
+                } else {
+                    Ok(Vec::new())
+                }
+            }
+            Err(err) => Err(SpfResult::TempError(err)),
+        }
+    }
+}
+
+fn in_network(ip: IpAddr, network: IpAddr, ip4_cidr: u8, ip6_cidr: u8) -> bool {
+    match (ip, network) {
+        (IpAddr::V4(ip), IpAddr::V4(network)) => {
+            let mask = u32::MAX.checked_shl(32 - u32::from(ip4_cidr)).unwrap_or(0);
+            u32::from(ip) & mask == u32::from(network) & mask
+        }
+        (IpAddr::V6(ip), IpAddr::V6(network)) => {
+            let mask = u128::MAX
+                .checked_shl(128 - u32::from(ip6_cidr))
+                .unwrap_or(0);
+            u128::from(ip) & mask == u128::from(network) & mask
+        }
+        _ => false,
+    }
+}
+
+fn is_subdomain_of(name: &str, domain: &str) -> bool {
+    let name = name.trim_end_matches('.');
+    name.eq_ignore_ascii_case(domain)
+        || name.len().checked_sub(domain.len() + 1).is_some_and(|pos| {
+            name.as_bytes()[pos] == b'.' && name[pos + 1..].eq_ignore_ascii_case(domain)
+        })
+}
+
+/// Whether `domain` has no empty labels and no label longer than 63
+/// characters (RFC 7208, section 4.3).
+fn is_valid_domain(domain: &str) -> bool {
+    domain.len() <= 253
+        && domain
+            .split('.')
+            .all(|label| !label.is_empty() && label.len() <= 63)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{common::resolver::Resolver, Error, MemoryResolver, SpfResult, SpfVerifier};
+    use std::{cell::RefCell, net::IpAddr};
+
+    fn ip(ip: &str) -> IpAddr {
+        ip.parse().unwrap()
+    }
+
+    fn check(resolver: &MemoryResolver, ip: &str, domain: &str) -> SpfResult {
+        SpfVerifier::new(resolver)
+            .check_host(
+                self::ip(ip),
+                domain,
+                &format!("user@{domain}"),
+                "mx.example.org",
+            )
+            .result
+    }
+
+    /// Records the TXT lookups made through a [`MemoryResolver`].
+    struct Recorder {
+        resolver: MemoryResolver,
+        txt: RefCell<Vec<String>>,
+    }
+
+    impl Resolver for Recorder {
+        fn txt_lookup(&self, name: &str) -> crate::Result<Vec<Vec<u8>>> {
+            self.txt.borrow_mut().push(name.to_string());
+            self.resolver.txt_lookup(name)
+        }
+    }
+
+    #[test]
+    fn lookup_limit() {
+        // a0.example includes a1.example, which includes a2.example and so on
+        let mut resolver = MemoryResolver::new();
+        for n in 0..12 {
+            resolver.txt_add(
+                format!("a{n}.example"),
+                format!("v=spf1 include:a{}.example -all", n + 1),
+            );
+        }
+        resolver.txt_add("a12.example", "v=spf1 +all");
+
+        assert_eq!(
+            check(&resolver, "192.0.2.1", "a0.example"),
+            SpfResult::PermError(Error::TooManyDnsLookups)
+        );
+        assert_eq!(
+            check(&resolver, "192.0.2.1", "a1.example"),
+            SpfResult::PermError(Error::TooManyDnsLookups)
+        );
+        // Exactly 10 lookups are allowed
+        assert_eq!(check(&resolver, "192.0.2.1", "a2.example"), SpfResult::Pass);
+    }
+
+    #[test]
+    fn void_lookup_limit() {
+        let mut resolver = MemoryResolver::new();
+        resolver.txt_add("two.example", "v=spf1 a:n1.example mx:n2.example +all");
+        resolver.txt_add(
+            "three.example",
+            "v=spf1 a:n1.example mx:n2.example exists:n3.example +all",
+        );
+
+        assert_eq!(
+            check(&resolver, "192.0.2.1", "two.example"),
+            SpfResult::Pass
+        );
+        assert_eq!(
+            check(&resolver, "192.0.2.1", "three.example"),
+            SpfResult::PermError(Error::TooManyVoidLookups)
+        );
+    }
+
+    #[test]
+    fn mx_limit() {
+        let mut resolver = MemoryResolver::new();
+        resolver.txt_add("ten.example", "v=spf1 mx -all");
+        resolver.txt_add("eleven.example", "v=spf1 mx -all");
+        for n in 0..11 {
+            if n < 10 {
+                resolver.mx_add("ten.example", n, format!("mx{n}.example"));
+            }
+            resolver.mx_add("eleven.example", n, format!("mx{n}.example"));
+            resolver.ip_add(format!("mx{n}.example"), ip(&format!("192.0.2.{n}")));
+        }
+
+        assert_eq!(
+            check(&resolver, "192.0.2.9", "ten.example"),
+            SpfResult::Pass
+        );
+        assert_eq!(
+            check(&resolver, "192.0.2.10", "ten.example"),
+            SpfResult::Fail
+        );
+        assert_eq!(
+            check(&resolver, "192.0.2.9", "eleven.example"),
+            SpfResult::PermError(Error::TooManyDnsLookups)
+        );
+    }
+
+    #[test]
+    fn include_redirect() {
+        let mut resolver = MemoryResolver::new();
+        resolver.txt_add("example.com", "v=spf1 include:_spf.example.net -all");
+        resolver.txt_add("_spf.example.net", "v=spf1 ip4:198.51.100.0/24 -all");
+        resolver.txt_add("example.org", "v=spf1 ip4:192.0.2.1 redirect=example.com");
+        resolver.txt_add("missing.example", "v=spf1 include:none.example -all");
+        resolver.txt_add("redirect.example", "v=spf1 redirect=none.example");
+        resolver.txt_add("temp.example", "v=spf1 include:error.example -all");
+        resolver.dns_error("error.example", Error::DnsError("timeout".to_string()));
+
+        assert_eq!(
+            check(&resolver, "198.51.100.7", "example.com"),
+            SpfResult::Pass
+        );
+        assert_eq!(
+            check(&resolver, "198.51.101.7", "example.com"),
+            SpfResult::Fail
+        );
+
+        // The redirect is only followed when no mechanism matched
+        assert_eq!(
+            check(&resolver, "192.0.2.1", "example.org"),
+            SpfResult::Pass
+        );
+        assert_eq!(
+            check(&resolver, "198.51.100.7", "example.org"),
+            SpfResult::Pass
+        );
+        assert_eq!(
+            check(&resolver, "192.0.2.2", "example.org"),
+            SpfResult::Fail
+        );
+
+        // Included or redirected domains without a record are permanent errors
+        assert_eq!(
+            check(&resolver, "192.0.2.1", "missing.example"),
+            SpfResult::PermError(Error::DnsRecordNotFound)
+        );
+        assert_eq!(
+            check(&resolver, "192.0.2.1", "redirect.example"),
+            SpfResult::PermError(Error::DnsRecordNotFound)
+        );
+        assert!(matches!(
+            check(&resolver, "192.0.2.1", "temp.example"),
+            SpfResult::TempError(_)
+        ));
+    }
+
+    #[test]
+    fn explanation() {
+        let mut resolver = MemoryResolver::new();
+        resolver.txt_add("example.com", "v=spf1 -all exp=explain._spf.%{d}");
+        resolver.txt_add(
+            "explain._spf.example.com",
+            "%{i} is not one of %{d}'s designated mail servers, see \
+             http://%{d}/why.html?s=%{S}&i=%{C}&r=%{r}",
+        );
+        let output = SpfVerifier::new(&resolver)
+            .receiver("mx.example.org")
+            .verify_mail_from(ip("192.0.2.3"), "helo.example", "<a b@example.com>");
+        assert_eq!(output.result, SpfResult::Fail);
+        assert_eq!(
+            output.explanation.as_deref(),
+            Some(
+                "192.0.2.3 is not one of example.com's designated mail servers, see \
+                 http://example.com/why.html?s=a%20b%40example.com&i=192.0.2.3&r=mx.example.org"
+            )
+        );
+
+        // Only a Fail is explained
+        resolver.txt_add("soft.example", "v=spf1 ~all exp=explain._spf.example.com");
+        let output = SpfVerifier::new(&resolver).check_host(
+            ip("192.0.2.3"),
+            "soft.example",
+            "a@soft.example",
+            "h",
+        );
+        assert_eq!(output.result, SpfResult::SoftFail);
+        assert_eq!(output.explanation, None);
+
+        // The explanation of a redirected record is used, but that of an
+        // included record is never looked up
+        resolver.txt_add("redirect.example", "v=spf1 redirect=example.com");
+        resolver.txt_add("include.example", "v=spf1 include:inner.example -all");
+        resolver.txt_add("inner.example", "v=spf1 -all exp=inner-explain.example");
+        resolver.txt_add("inner-explain.example", "Not explained");
+        let output = SpfVerifier::new(&resolver).check_host(
+            ip("192.0.2.3"),
+            "redirect.example",
+            "a@redirect.example",
+            "h",
+        );
+        assert_eq!(output.result, SpfResult::Fail);
+        assert!(output.explanation.is_some());
+
+        let recorder = Recorder {
+            resolver,
+            txt: RefCell::new(Vec::new()),
+        };
+        let output = SpfVerifier::new(&recorder).check_host(
+            ip("192.0.2.3"),
+            "include.example",
+            "a@include.example",
+            "h",
+        );
+        assert_eq!(output.result, SpfResult::Fail);
+        assert_eq!(output.explanation, None);
+        assert_eq!(*recorder.txt.borrow(), ["include.example", "inner.example"]);
+    }
+
+    #[test]
+    fn macros() {
+        // RFC 7208, section 7.4
+        let cases = [
+            ("%{s}", "strong-bad@email.example.com"),
+            ("%{o}", "email.example.com"),
+            ("%{d}", "email.example.com"),
+            ("%{d4}", "email.example.com"),
+            ("%{d3}", "email.example.com"),
+            ("%{d2}", "example.com"),
+            ("%{d1}", "com"),
+            ("%{dr}", "com.example.email"),
+            ("%{d2r}", "example.email"),
+            ("%{l}", "strong-bad"),
+            ("%{l-}", "strong.bad"),
+            ("%{lr}", "strong-bad"),
+            ("%{lr-}", "bad.strong"),
+            ("%{l1r-}", "strong"),
+            (
+                "%{ir}.%{v}._spf.%{d2}",
+                "3.2.0.192.in-addr._spf.example.com",
+            ),
+            ("%{lr-}.lp._spf.%{d2}", "bad.strong.lp._spf.example.com"),
+            (
+                "%{lr-}.lp.%{ir}.%{v}._spf.%{d2}",
+                "bad.strong.lp.3.2.0.192.in-addr._spf.example.com",
+            ),
+            (
+                "%{ir}.%{v}.%{l1r-}.lp._spf.%{d2}",
+                "3.2.0.192.in-addr.strong.lp._spf.example.com",
+            ),
+            (
+                "%{d2}.trusted-domains.example.net",
+                "example.com.trusted-domains.example.net",
+            ),
+            ("%{h}.helo", "helo.example.org.helo"),
+            ("%{p}.ptr", "unknown.ptr"),
+        ];
+        for (spec, expected) in cases {
+            let mut resolver = MemoryResolver::new();
+            resolver.txt_add("email.example.com", format!("v=spf1 exists:{spec} -all"));
+            resolver.ip_add(expected, ip("127.0.0.2"));
+            let output = SpfVerifier::new(resolver).verify_mail_from(
+                ip("192.0.2.3"),
+                "helo.example.org",
+                "strong-bad@email.example.com",
+            );
+            assert_eq!(output.result, SpfResult::Pass, "{spec}");
+        }
+
+        // IPv6 addresses are expanded nibble by nibble
+        let mut resolver = MemoryResolver::new();
+        resolver.txt_add(
+            "email.example.com",
+            "v=spf1 exists:%{ir}.%{v}._spf.%{d2} -all",
+        );
+        resolver.ip_add(
+            "1.0.b.c.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.8.b.d.0.1.0.0.2.ip6._spf.example.com",
+            ip("127.0.0.2"),
+        );
+        let output = SpfVerifier::new(resolver).verify_mail_from(
+            ip("2001:db8::cb01"),
+            "helo.example.org",
+            "strong-bad@email.example.com",
+        );
+        assert_eq!(output.result, SpfResult::Pass);
+    }
+}